@@ -0,0 +1,57 @@
+//! Compare how deep `BasicAi` searches in a fixed amount of time with each of its optional search
+//! techniques enabled and disabled, from the starting position of each out-of-the-box variant.
+//!
+//! Run with `cargo bench --bench search [-- <seconds per search>]`.
+
+use hnefatafl::aliases::MediumBasicGame;
+use std::time::Duration;
+use taflr::ai::{Ai, BasicAi, CancelToken};
+use taflr::variants::OOTB_VARIANTS;
+
+/// Configurations to compare: (description, null-move pruning, late move reductions).
+const CONFIGS: [(&str, bool, bool); 4] = [
+    ("NMP + LMR", true, true),
+    ("NMP only", true, false),
+    ("LMR only", false, true),
+    ("neither", false, false),
+];
+
+const DEFAULT_SECS: u64 = 5;
+
+fn main() {
+    // `cargo bench` passes flags such as `--bench`, which we ignore
+    let secs = std::env::args()
+        .skip(1)
+        .find(|a| !a.starts_with('-'))
+        .map(|a| a.parse().expect("Time per search should be a whole number of seconds"))
+        .unwrap_or(DEFAULT_SECS);
+    let time_to_play = Duration::from_secs(secs);
+
+    println!("{:<12} {:<10} {:>6} {:>12} {:>12} {:>10}", "Variant", "Config", "Depth", "Nodes",
+             "Nodes/s", "Depth/s");
+    for (rules, board, name, eval_params) in OOTB_VARIANTS {
+        let game = MediumBasicGame::new(rules, board).expect("Could not construct game");
+        for (desc, nmp, lmr) in CONFIGS {
+            let mut ai = BasicAi::new(game.logic);
+            ai.eval_params = eval_params;
+            ai.null_move_pruning = nmp;
+            ai.late_move_reductions = lmr;
+            let (_, info) = ai.next_play(
+                &game.state,
+                &game.position_history,
+                time_to_play,
+                &CancelToken::new(),
+                &mut |_| {}
+            ).expect("AI could not find a play");
+            println!(
+                "{:<12} {:<10} {:>6} {:>12} {:>12.0} {:>10.2}",
+                name,
+                desc,
+                info.stats.max_depth,
+                info.stats.states,
+                info.nodes_per_sec(),
+                info.stats.max_depth as f64 / info.elapsed.as_secs_f64()
+            );
+        }
+    }
+}
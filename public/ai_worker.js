@@ -0,0 +1,3 @@
+// Loads the AI web worker built from src/bin/ai_worker.rs (see the README).
+importScripts("/ai_worker/ai_worker.js");
+wasm_bindgen("/ai_worker/ai_worker_bg.wasm");
@@ -0,0 +1,102 @@
+use crate::ai::BasicAi;
+use hnefatafl::board::state::BoardState;
+use hnefatafl::game::state::GameState;
+use hnefatafl::play::{Play, ValidPlay};
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Default name of the file in which opening books are stored (see [`load_opening_books`]).
+pub const OPENING_BOOK_FILE: &str = "opening_book.json";
+
+/// A play recommended by an [`OpeningBook`].
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct BookPlay {
+    /// The play, in the same notation as stored in the database.
+    pub play: String,
+    /// How often the play should be chosen, relative to the other plays in the same position.
+    pub weight: u32,
+}
+
+/// Plays to make in the opening positions of a variant, so that the AI doesn't spend its time
+/// searching positions it has seen many times before, and doesn't play the same line every game.
+///
+/// Positions are keyed by their Zobrist hash (see [`BasicAi::book_hash`]), which is the same in
+/// every session.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct OpeningBook {
+    positions: HashMap<u64, Vec<BookPlay>>,
+}
+
+impl OpeningBook {
+    /// Add `weight` to the weight of `play` in the position with the given hash, adding the play
+    /// if it isn't already in the book.
+    pub fn add(&mut self, hash: u64, play: Play, weight: u32) {
+        let play = play.to_string();
+        let plays = self.positions.entry(hash).or_default();
+        match plays.iter_mut().find(|bp| bp.play == play) {
+            Some(bp) => bp.weight += weight,
+            None => plays.push(BookPlay { play, weight })
+        }
+    }
+
+    /// The plays recommended in the position with the given hash.
+    pub fn plays(&self, hash: u64) -> &[BookPlay] {
+        self.positions.get(&hash).map_or(&[], |p| p.as_slice())
+    }
+
+    /// Remove plays with less than `min_weight` weight, and any positions left with no plays.
+    pub fn prune(&mut self, min_weight: u32) {
+        for plays in self.positions.values_mut() {
+            plays.retain(|bp| bp.weight >= min_weight);
+        }
+        self.positions.retain(|_, plays| !plays.is_empty());
+    }
+
+    /// Number of positions in the book.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+}
+
+/// Load opening books, keyed by variant name, from a JSON file.
+pub fn load_opening_books(path: &Path) -> io::Result<HashMap<String, OpeningBook>> {
+    let json = std::fs::read_to_string(path)?;
+    serde_json::from_str(&json).map_err(io::Error::other)
+}
+
+/// Save opening books, keyed by variant name, to a JSON file.
+pub fn save_opening_books(path: &Path, books: &HashMap<String, OpeningBook>) -> io::Result<()> {
+    let json = serde_json::to_string(books).map_err(io::Error::other)?;
+    std::fs::write(path, json)
+}
+
+impl<T: BoardState> BasicAi<T> {
+    /// The key of `state` in an [`OpeningBook`].
+    pub fn book_hash(&self, state: &GameState<T>) -> u64 {
+        self.zt.hash(state.board, state.side_to_play)
+    }
+
+    /// A play from [`BasicAi::opening_book`] for `state`, if it has any, chosen at random
+    /// according to the plays' weights. Plays which aren't legal in `state` are ignored.
+    pub(crate) fn book_play(&self, state: &GameState<T>) -> Option<ValidPlay> {
+        let book = self.opening_book.as_ref()?;
+        let candidates: Vec<(ValidPlay, u32)> = book.plays(self.book_hash(state)).iter()
+            .filter_map(|bp| {
+                let play = Play::from_str(&bp.play).ok()?;
+                let vp = self.logic.iter_plays(play.from, state).ok()?
+                    .find(|vp| vp.play == play)?;
+                Some((vp, bp.weight))
+            })
+            .collect();
+        candidates.choose_weighted(&mut thread_rng(), |(_, w)| *w).ok().map(|(vp, _)| *vp)
+    }
+}
@@ -0,0 +1,119 @@
+use crate::ai::BasicAi;
+use hnefatafl::board::state::BoardState;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// A named strength level for [`BasicAi`]. Weaker levels search less deeply, add noise to the
+/// evaluation and occasionally play a random play instead of the best one, so that the AI makes
+/// the kind of mistakes a human opponent might.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default, Serialize, Deserialize)]
+pub enum Difficulty {
+    Beginner,
+    Casual,
+    Club,
+    Strong,
+    /// Full strength, limited only by the time given to the AI.
+    #[default]
+    Max,
+}
+
+impl Difficulty {
+    /// All levels, from weakest to strongest.
+    pub const ALL: [Difficulty; 5] = [
+        Difficulty::Beginner,
+        Difficulty::Casual,
+        Difficulty::Club,
+        Difficulty::Strong,
+        Difficulty::Max,
+    ];
+
+    /// Name of the level, for display to the user.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Difficulty::Beginner => "Beginner",
+            Difficulty::Casual => "Casual",
+            Difficulty::Club => "Club",
+            Difficulty::Strong => "Strong",
+            Difficulty::Max => "Max",
+        }
+    }
+
+    /// Maximum search depth.
+    fn max_depth(&self) -> Option<u8> {
+        match self {
+            Difficulty::Beginner => Some(1),
+            Difficulty::Casual => Some(2),
+            Difficulty::Club => Some(4),
+            Difficulty::Strong => Some(8),
+            Difficulty::Max => None,
+        }
+    }
+
+    /// Number of nodes after which no new iteration of the search is started.
+    fn node_limit(&self) -> Option<u64> {
+        match self {
+            Difficulty::Beginner => Some(2_000),
+            Difficulty::Casual => Some(20_000),
+            Difficulty::Club => Some(200_000),
+            Difficulty::Strong | Difficulty::Max => None,
+        }
+    }
+
+    /// Maximum amount of random noise added to (or subtracted from) each evaluation.
+    fn eval_noise(&self) -> i32 {
+        match self {
+            Difficulty::Beginner => 60,
+            Difficulty::Casual => 30,
+            Difficulty::Club => 10,
+            Difficulty::Strong | Difficulty::Max => 0,
+        }
+    }
+
+    /// Probability of making a random play instead of the best play found.
+    fn blunder_chance(&self) -> f64 {
+        match self {
+            Difficulty::Beginner => 0.25,
+            Difficulty::Casual => 0.1,
+            Difficulty::Club => 0.03,
+            Difficulty::Strong | Difficulty::Max => 0.0,
+        }
+    }
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difficulty::Beginner => write!(f, "beginner"),
+            Difficulty::Casual => write!(f, "casual"),
+            Difficulty::Club => write!(f, "club"),
+            Difficulty::Strong => write!(f, "strong"),
+            Difficulty::Max => write!(f, "max"),
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "beginner" => Ok(Difficulty::Beginner),
+            "casual" => Ok(Difficulty::Casual),
+            "club" => Ok(Difficulty::Club),
+            "strong" => Ok(Difficulty::Strong),
+            "max" => Ok(Difficulty::Max),
+            other => Err(format!("Unknown difficulty: {other}"))
+        }
+    }
+}
+
+impl<T: BoardState> BasicAi<T> {
+    /// Configure the AI to play at the given level, overriding [`BasicAi::max_depth`],
+    /// [`BasicAi::node_limit`], [`BasicAi::eval_noise`] and [`BasicAi::blunder_chance`].
+    pub fn set_difficulty(&mut self, difficulty: Difficulty) {
+        self.max_depth = difficulty.max_depth();
+        self.node_limit = difficulty.node_limit();
+        self.eval_noise = difficulty.eval_noise();
+        self.blunder_chance = difficulty.blunder_chance();
+    }
+}
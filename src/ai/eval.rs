@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+/// Number of weights in [`EvalParams`].
+pub const N_EVAL_PARAMS: usize = 9;
+
+/// Default name of the file to which tuned parameters are written, and from which the app loads
+/// them.
+pub const TUNED_PARAMS_FILE: &str = "eval_params.json";
+
+/// Weights for the terms of [`super::BasicAi::eval_board`]. Each term is measured so that a
+/// positive weight means "more is better for the side the term names".
+///
+/// Different variants reward different things (eg, a piece is worth more on a small board where
+/// each side has fewer of them), so each variant has its own parameters, which are stored in the
+/// database alongside its rules.
+///
+/// Parameters missing from stored data (eg, because they were saved before a term was added) take
+/// their default values.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvalParams {
+    /// Value of each attacking piece, to the attacker.
+    pub attacker_piece: i32,
+    /// Value of each defending piece (not including the king), to the defender.
+    pub defender_piece: i32,
+    /// Value of each piece on the board (of either side), to the attacker, who benefits more from
+    /// a crowded board.
+    pub piece_total: i32,
+    /// Value to the attacker of each row and column separating the king from the nearest edge.
+    pub king_edge_dist: i32,
+    /// Value to the attacker of each of the king's neighbouring tiles that is hostile to it.
+    pub king_hostile_neighbor: i32,
+    /// Value to the defender of each unit of average Manhattan distance between the attacking
+    /// pieces and the king.
+    pub attacker_king_dist: i32,
+    /// Value to the defender of the king having a single open line to an escape tile.
+    pub escape_route: i32,
+    /// Value to the defender of the king having two or more open lines to escape tiles, which the
+    /// attacker can't block in one play. Should be large, but well short of a win.
+    pub double_escape_route: i32,
+    /// Value to the attacker of each attacking piece next to a corner (in corner escape games).
+    pub corner_blockade: i32,
+}
+
+impl EvalParams {
+    /// Parameters for Copenhagen (11x11, corner escape). These are also the defaults for custom
+    /// variants.
+    pub const COPENHAGEN: Self = Self {
+        attacker_piece: 10,
+        defender_piece: 20,
+        piece_total: 1,
+        king_edge_dist: 5,
+        king_hostile_neighbor: 10,
+        attacker_king_dist: 10,
+        escape_route: 100,
+        double_escape_route: 2000,
+        corner_blockade: 15,
+    };
+
+    /// Parameters for Tablut (9x9). The board is smaller, so the king is never far from an edge
+    /// and each step towards one matters more.
+    pub const TABLUT: Self = Self {
+        attacker_piece: 12,
+        defender_piece: 24,
+        piece_total: 1,
+        king_edge_dist: 8,
+        king_hostile_neighbor: 12,
+        attacker_king_dist: 8,
+        escape_route: 120,
+        double_escape_route: 2000,
+        corner_blockade: 10,
+    };
+
+    /// Parameters for Brandubh (7x7, corner escape). Each side has few pieces, so each one counts
+    /// for more, and every attacker is near the king anyway.
+    pub const BRANDUBH: Self = Self {
+        attacker_piece: 20,
+        defender_piece: 35,
+        piece_total: 2,
+        king_edge_dist: 10,
+        king_hostile_neighbor: 15,
+        attacker_king_dist: 12,
+        escape_route: 150,
+        double_escape_route: 2500,
+        corner_blockade: 25,
+    };
+
+    /// Parameters for Magpie.
+    pub const MAGPIE: Self = Self {
+        attacker_piece: 20,
+        defender_piece: 30,
+        piece_total: 2,
+        king_edge_dist: 12,
+        king_hostile_neighbor: 15,
+        attacker_king_dist: 12,
+        escape_route: 150,
+        double_escape_route: 2500,
+        corner_blockade: 25,
+    };
+}
+
+impl EvalParams {
+    /// Names of the weights, in the order used by [`EvalParams::to_array`].
+    pub const NAMES: [&'static str; N_EVAL_PARAMS] = [
+        "attacker_piece",
+        "defender_piece",
+        "piece_total",
+        "king_edge_dist",
+        "king_hostile_neighbor",
+        "attacker_king_dist",
+        "escape_route",
+        "double_escape_route",
+        "corner_blockade",
+    ];
+
+    /// The weights as an array, so that they can be adjusted generically (eg, by a tuner).
+    pub fn to_array(&self) -> [i32; N_EVAL_PARAMS] {
+        [
+            self.attacker_piece,
+            self.defender_piece,
+            self.piece_total,
+            self.king_edge_dist,
+            self.king_hostile_neighbor,
+            self.attacker_king_dist,
+            self.escape_route,
+            self.double_escape_route,
+            self.corner_blockade,
+        ]
+    }
+
+    /// The inverse of [`EvalParams::to_array`].
+    pub fn from_array(a: [i32; N_EVAL_PARAMS]) -> Self {
+        Self {
+            attacker_piece: a[0],
+            defender_piece: a[1],
+            piece_total: a[2],
+            king_edge_dist: a[3],
+            king_hostile_neighbor: a[4],
+            attacker_king_dist: a[5],
+            escape_route: a[6],
+            double_escape_route: a[7],
+            corner_blockade: a[8],
+        }
+    }
+}
+
+/// Read a file of tuned parameters, as written by [`save_tuned_params`], mapping variant names to
+/// their parameters.
+pub fn load_tuned_params(path: &Path) -> io::Result<HashMap<String, EvalParams>> {
+    let json = std::fs::read_to_string(path)?;
+    serde_json::from_str(&json).map_err(io::Error::other)
+}
+
+/// Write tuned parameters for each of the named variants to a file.
+pub fn save_tuned_params(path: &Path, params: &HashMap<String, EvalParams>) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(params).map_err(io::Error::other)?;
+    std::fs::write(path, json)
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        Self::COPENHAGEN
+    }
+}
@@ -0,0 +1,282 @@
+use crate::ai::{
+    legal_plays, should_stop, Ai, AiError, CancelToken, DepthInfo, SearchInfo, SearchProgress
+};
+use hnefatafl::board::state::BoardState;
+use hnefatafl::game::logic::GameLogic;
+use hnefatafl::game::state::{GameState, Position};
+use hnefatafl::game::GameOutcome::Win;
+use hnefatafl::game::GameStatus::{Ongoing, Over};
+use hnefatafl::pieces::Side;
+use hnefatafl::pieces::Side::{Attacker, Defender};
+use hnefatafl::play::ValidPlay;
+use rand::rngs::ThreadRng;
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant};
+#[cfg(target_arch = "wasm32")]
+use web_time::{Duration, Instant};
+
+/// Default value for [`MctsAi::exploration`] (approximately the square root of 2, the
+/// theoretically optimal value for rewards between 0 and 1).
+const DEFAULT_EXPLORATION: f64 = 1.4;
+
+/// Default value for [`MctsAi::max_playout_plies`].
+const DEFAULT_MAX_PLAYOUT_PLIES: usize = 200;
+
+/// Number of iterations between progress updates.
+const PROGRESS_INTERVAL: u64 = 1000;
+
+/// Maximum length of the principal variation reported after a search.
+const MAX_PV_LEN: usize = 8;
+
+/// A node in the search tree. Nodes don't store their game state; it is recomputed by replaying
+/// plays from the root on each iteration.
+struct Node {
+    /// The play leading to this node (`None` for the root).
+    play: Option<ValidPlay>,
+    /// The side that made `play`, to whom `reward` is credited.
+    side: Side,
+    /// Indices of child nodes in the tree.
+    children: Vec<usize>,
+    /// Legal plays from this node that don't yet have a child node, in random order. `None` until
+    /// the node is first visited.
+    untried: Option<Vec<ValidPlay>>,
+    /// Number of playouts that have passed through this node.
+    visits: u32,
+    /// Total reward of those playouts for `side` (1 for a win, 0.5 for a draw, 0 for a loss).
+    reward: f64,
+}
+
+impl Node {
+    fn new(play: Option<ValidPlay>, side: Side) -> Self {
+        Self { play, side, children: Vec::new(), untried: None, visits: 0, reward: 0.0 }
+    }
+
+    /// Average reward of playouts through this node, for the side that made its play.
+    fn mean_reward(&self) -> f64 {
+        if self.visits == 0 { 0.0 } else { self.reward / self.visits as f64 }
+    }
+}
+
+/// An AI which uses Monte Carlo tree search (specifically UCT), estimating the value of plays by
+/// playing random games from them, and spending more time on the plays that look most promising.
+/// Unlike [`super::BasicAi`], it doesn't use any knowledge of tafl beyond the rules.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MctsAi<T: BoardState> {
+    logic: GameLogic<T>,
+    /// Weight given to exploring less-visited plays, relative to exploiting plays which have done
+    /// well so far.
+    pub exploration: f64,
+    /// Playouts which haven't ended after this many plies are scored as draws.
+    pub max_playout_plies: usize,
+}
+
+impl<T: BoardState> MctsAi<T> {
+    pub fn new(logic: GameLogic<T>) -> Self {
+        Self {
+            logic,
+            exploration: DEFAULT_EXPLORATION,
+            max_playout_plies: DEFAULT_MAX_PLAYOUT_PLIES,
+        }
+    }
+
+    /// All legal plays in `state`, in random order.
+    fn shuffled_plays(&self, state: &GameState<T>, rng: &mut ThreadRng) -> Vec<ValidPlay> {
+        let mut plays = legal_plays(&self.logic, state);
+        plays.shuffle(rng);
+        plays
+    }
+
+    /// The reward for `side` of a game which ended (or was abandoned) in `state`.
+    fn reward(state: &GameState<T>, side: Side) -> f64 {
+        match state.status {
+            Over(Win(_, winner)) if winner == side => 1.0,
+            Over(Win(_, _)) => 0.0,
+            _ => 0.5
+        }
+    }
+
+    /// The child of `node` with the highest upper confidence bound.
+    fn select_child(&self, tree: &[Node], node: usize) -> usize {
+        let ln_visits = (tree[node].visits as f64).ln();
+        let ucb = |i: usize| {
+            let child = &tree[i];
+            child.mean_reward()
+                + self.exploration * (ln_visits / child.visits as f64).sqrt()
+        };
+        *tree[node].children.iter()
+            .max_by(|a, b| ucb(**a).total_cmp(&ucb(**b)))
+            .expect("Node has no children")
+    }
+
+    /// Play random plays from `state` until the game ends or the playout limit is reached.
+    /// `posn_history` should end with `state`, and has each position reached added to it.
+    fn playout(
+        &self,
+        mut state: GameState<T>,
+        posn_history: &mut Vec<Position<T>>,
+        rng: &mut ThreadRng
+    ) -> GameState<T> {
+        for _ in 0..self.max_playout_plies {
+            if state.status != Ongoing {
+                break
+            }
+            let Some(play) = legal_plays(&self.logic, &state).choose(rng).copied() else {
+                break
+            };
+            state = self.logic.do_valid_play(play, state, Some(posn_history)).new_state;
+            posn_history.push((&state).into());
+        }
+        state
+    }
+
+    /// Run one iteration of the search: select a path through the tree, expand it by one node,
+    /// play out a random game from there and credit the result to every node on the path.
+    /// Returns the depth of the new node. `posn_history` should end with `root_state`, as the
+    /// game's history does.
+    fn iterate(
+        &self,
+        tree: &mut Vec<Node>,
+        root_state: GameState<T>,
+        posn_history: &mut Vec<Position<T>>,
+        rng: &mut ThreadRng
+    ) -> u8 {
+        let mut state = root_state;
+        let mut path = vec![0];
+        let mut node = 0;
+
+        // Selection: descend through fully expanded nodes
+        loop {
+            if state.status != Ongoing {
+                break
+            }
+            if tree[node].untried.is_none() {
+                tree[node].untried = Some(self.shuffled_plays(&state, rng));
+            }
+            if let Some(play) = tree[node].untried.as_mut().and_then(|u| u.pop()) {
+                // Expansion: add a child for one untried play
+                let child = Node::new(Some(play), state.side_to_play);
+                state = self.logic.do_valid_play(play, state, Some(posn_history)).new_state;
+                posn_history.push((&state).into());
+                tree.push(child);
+                let child_i = tree.len() - 1;
+                tree[node].children.push(child_i);
+                path.push(child_i);
+                break
+            }
+            if tree[node].children.is_empty() {
+                break
+            }
+            node = self.select_child(tree, node);
+            let play = tree[node].play.expect("Non-root node has no play");
+            state = self.logic.do_valid_play(play, state, Some(posn_history)).new_state;
+            posn_history.push((&state).into());
+            path.push(node);
+        }
+
+        // Simulation
+        let end_state = self.playout(state, posn_history, rng);
+
+        // Backpropagation
+        let att_reward = Self::reward(&end_state, Attacker);
+        for i in &path {
+            let n = &mut tree[*i];
+            n.visits += 1;
+            n.reward += if n.side == Attacker { att_reward } else { 1.0 - att_reward };
+        }
+        (path.len() - 1).min(u8::MAX as usize) as u8
+    }
+
+    /// The most visited child of `node`, if any.
+    fn most_visited_child(tree: &[Node], node: usize) -> Option<usize> {
+        tree[node].children.iter().copied().max_by_key(|i| tree[*i].visits)
+    }
+
+    /// The line of play the search considers most likely, following the most visited children.
+    fn principal_variation(tree: &[Node]) -> Vec<ValidPlay> {
+        let mut pv = Vec::new();
+        let mut node = 0;
+        while let Some(child) = Self::most_visited_child(tree, node) {
+            if pv.len() >= MAX_PV_LEN || tree[child].visits == 0 {
+                break
+            }
+            pv.push(tree[child].play.expect("Non-root node has no play"));
+            node = child;
+        }
+        pv
+    }
+
+    /// A score for the most visited root play, on roughly the same scale as [`super::BasicAi`]'s
+    /// (positive = good for attacker), derived from the proportion of playouts won.
+    fn score(tree: &[Node], best: usize) -> i32 {
+        let att_reward = if tree[best].side == Attacker {
+            tree[best].mean_reward()
+        } else {
+            1.0 - tree[best].mean_reward()
+        };
+        ((att_reward - 0.5) * 2000.0) as i32
+    }
+}
+
+impl<T: BoardState> Ai for MctsAi<T> {
+
+    type BoardState = T;
+
+    fn next_play(
+        &mut self,
+        game_state: &GameState<T>,
+        posn_history: &[Position<T>],
+        time_to_play: Duration,
+        cancel: &CancelToken,
+        on_progress: &mut dyn FnMut(&SearchProgress)
+    ) -> Result<(ValidPlay, SearchInfo), AiError> {
+        if game_state.status != Ongoing {
+            return Err(AiError::NoPlayAvailable);
+        }
+        let start_time = Instant::now();
+        let cutoff_time = start_time + time_to_play;
+        let mut rng = thread_rng();
+        let root_side = if game_state.side_to_play == Attacker { Defender } else { Attacker };
+        let mut tree = vec![Node::new(None, root_side)];
+        let mut posns = Vec::with_capacity(posn_history.len() + self.max_playout_plies);
+        let mut info = SearchInfo::default();
+
+        while !should_stop(cancel, cutoff_time) {
+            posns.clear();
+            posns.extend_from_slice(posn_history);
+            let depth = self.iterate(&mut tree, *game_state, &mut posns, &mut rng);
+            info.stats.states += 1;
+            info.stats.max_depth = info.stats.max_depth.max(depth);
+            if info.stats.states % PROGRESS_INTERVAL == 0 {
+                let best = Self::most_visited_child(&tree, 0)
+                    .map(|c| (tree[c].play.expect("Non-root node has no play"),
+                              Self::score(&tree, c)));
+                on_progress(&SearchProgress {
+                    depth: info.stats.max_depth,
+                    nodes: info.stats.states,
+                    elapsed: start_time.elapsed(),
+                    best
+                });
+            }
+            if tree[0].children.is_empty() {
+                // The root had no legal plays to expand
+                break
+            }
+        }
+        if cancel.is_cancelled() {
+            return Err(AiError::Cancelled);
+        }
+
+        let best = Self::most_visited_child(&tree, 0).ok_or(AiError::NoPlayAvailable)?;
+        info.elapsed = start_time.elapsed();
+        info.depths.push(DepthInfo {
+            depth: info.stats.max_depth,
+            score: Self::score(&tree, best),
+            pv: Self::principal_variation(&tree),
+            nodes: info.stats.states,
+            elapsed: info.elapsed
+        });
+        Ok((info.depths[0].best_play(), info))
+    }
+}
@@ -0,0 +1,1545 @@
+mod book;
+mod difficulty;
+mod eval;
+mod mcts;
+mod tablebase;
+mod tt;
+
+use crate::ai::tt::{NodeType, TranspositionTable};
+pub use crate::ai::book::{
+    load_opening_books, save_opening_books, BookPlay, OpeningBook, OPENING_BOOK_FILE
+};
+pub use crate::ai::difficulty::Difficulty;
+pub use crate::ai::mcts::MctsAi;
+pub use crate::ai::tablebase::{tablebase_path, Tablebase, TbResult, TABLEBASE_DIR};
+pub use crate::ai::eval::{
+    load_tuned_params, save_tuned_params, EvalParams, N_EVAL_PARAMS, TUNED_PARAMS_FILE
+};
+use crate::ai::AiError::NoPlayAvailable;
+use hnefatafl::board::state::BoardState;
+use hnefatafl::game::logic::GameLogic;
+use hnefatafl::game::state::{GameState, Position};
+use hnefatafl::game::GameOutcome::{Draw, Win};
+use hnefatafl::game::GameStatus::{Ongoing, Over};
+use hnefatafl::pieces;
+use hnefatafl::pieces::PieceType::{King, Soldier};
+use hnefatafl::pieces::Side::{Attacker, Defender};
+use hnefatafl::pieces::{Piece, KING};
+use hnefatafl::play::ValidPlay;
+use hnefatafl::tiles::{Coords, Tile};
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::cmp::min;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant};
+#[cfg(target_arch = "wasm32")]
+use web_time::{Duration, Instant};
+use hnefatafl::collections::PieceMap;
+
+/// Counters describing the work done during a search.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SearchStats {
+    /// Number of nodes (game states) visited.
+    pub states: u64,
+    /// Number of leaf nodes of the main search evaluated.
+    pub paths: u64,
+    /// Number of nodes visited during quiescence search.
+    pub q_states: u64,
+    /// Number of times a usable entry was found in the transposition table.
+    pub tt_hits: u64,
+    /// Number of entries in the transposition table that were overwritten.
+    pub tt_replacements: u64,
+    /// Number of entries added to empty slots in the transposition table.
+    pub tt_inserts: u64,
+    /// Number of positions whose score was found in the endgame tablebase.
+    pub tb_hits: u64,
+    /// Number of alpha-beta cutoffs.
+    pub ab_prunes: u64,
+    /// Number of nodes pruned by null-move pruning.
+    pub null_move_prunes: u64,
+    /// Number of plays searched to a reduced depth by late move reductions.
+    pub lmr_reductions: u64,
+    /// Number of times a play had to be re-searched with a full window after a null window search
+    /// showed it might be better than the best play found so far.
+    pub pvs_researches: u64,
+    /// Number of times the root had to be re-searched because the score fell outside the
+    /// aspiration window.
+    pub aspiration_researches: u64,
+    /// Deepest iteration that was fully completed.
+    pub max_depth: u8
+}
+
+/// The result of searching to a particular depth.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DepthInfo {
+    /// The depth searched.
+    pub depth: u8,
+    /// The score of the best play found. Higher = better for attacker, lower = better for
+    /// defender.
+    pub score: i32,
+    /// The principal variation, ie, the line of play the AI expects, starting with its own play.
+    /// Never empty.
+    pub pv: Vec<ValidPlay>,
+    /// Total number of nodes visited by the search by the time this iteration completed.
+    pub nodes: u64,
+    /// Time elapsed from the start of the search to the completion of this iteration.
+    pub elapsed: Duration
+}
+
+impl DepthInfo {
+    /// The best play found at this depth.
+    pub fn best_play(&self) -> ValidPlay {
+        self.pv[0]
+    }
+}
+
+/// Information about a completed search.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct SearchInfo {
+    /// Results for each fully completed iteration of the search, in order of increasing depth.
+    pub depths: Vec<DepthInfo>,
+    /// Counters describing the work done by the search.
+    pub stats: SearchStats,
+    /// Total time taken by the search.
+    pub elapsed: Duration
+}
+
+impl SearchInfo {
+    /// The principal variation found by the deepest completed iteration of the search.
+    pub fn principal_variation(&self) -> &[ValidPlay] {
+        self.depths.last().map_or(&[], |d| d.pv.as_slice())
+    }
+
+    /// Average number of nodes visited per second.
+    pub fn nodes_per_sec(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 { self.stats.states as f64 / secs } else { 0.0 }
+    }
+
+    /// If the deepest completed iteration of the search found a forced win, the winning side and
+    /// the number of plays that side needs to make to win (including the one about to be made, if
+    /// it is the winner's turn).
+    pub fn forced_win(&self) -> Option<(pieces::Side, u8)> {
+        let score = self.depths.last()?.score;
+        if !is_win_score(score) {
+            return None
+        }
+        let plies = WIN_SCORE - score.abs();
+        let winner = if score > 0 { Attacker } else { Defender };
+        Some((winner, ((plies + 1) / 2) as u8))
+    }
+}
+
+impl Display for SearchInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for d in &self.depths {
+            writeln!(f, "Depth {}: {} (score: {})", d.depth, d.best_play(), d.score)?;
+        }
+        if let Some((winner, n)) = self.forced_win() {
+            let winner = if winner == Attacker { "attacker" } else { "defender" };
+            writeln!(f, "Forced win for {winner} in {n}.")?;
+        }
+        writeln!(f, "Searched {} paths ({} states) in {:.3}s ({:.0} states/s).",
+                 self.stats.paths, self.stats.states, self.elapsed.as_secs_f32(), self.nodes_per_sec())?;
+        writeln!(f, "Quiescence search visited {} states.", self.stats.q_states)?;
+        writeln!(f, "Maximum depth searched: {}", self.stats.max_depth)?;
+        writeln!(f, "Pruned {} paths ({} by null move).", self.stats.ab_prunes, self.stats.null_move_prunes)?;
+        writeln!(f, "Reduced {} late plays.", self.stats.lmr_reductions)?;
+        writeln!(f, "Re-searches: {} (PVS), {} (aspiration).",
+                 self.stats.pvs_researches, self.stats.aspiration_researches)?;
+        writeln!(f, "Tablebase hits: {}.", self.stats.tb_hits)?;
+        write!(f, "TT hits: {}, insertions: {}, replacements: {}.",
+               self.stats.tt_hits, self.stats.tt_inserts, self.stats.tt_replacements)
+    }
+}
+
+/// A snapshot of a search that is still under way, for displaying while the AI is thinking.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct SearchProgress {
+    /// The depth currently being searched.
+    pub depth: u8,
+    /// Number of nodes visited so far.
+    pub nodes: u64,
+    /// Time elapsed since the search began.
+    pub elapsed: Duration,
+    /// The best play found by the deepest completed iteration so far, and its score.
+    pub best: Option<(ValidPlay, i32)>
+}
+
+#[derive(Debug)]
+pub enum AiError {
+    NoPlayAvailable,
+    NotMyTurn,
+    /// The search was cancelled before it could find a play.
+    Cancelled
+}
+
+/// A token that can be used to cancel an in-progress search, including from another thread.
+/// Clones of a token share the same state, so cancelling any clone cancels them all.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Signal that any search using this token should stop as soon as possible.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether this token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl PartialEq for CancelToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for CancelToken {}
+
+/// Score of a position in which the attacker has won (the defender's win is the negation). A win
+/// found `n` plies from the root scores `WIN_SCORE - n`, so that quicker wins (and slower losses)
+/// are preferred; see [`BasicAi::eval_state`].
+const WIN_SCORE: i32 = 1_000_000;
+
+/// Scores at least this far from zero are wins (or losses), whose distance from `WIN_SCORE` is the
+/// number of plies to the end of the game.
+const WIN_THRESHOLD: i32 = WIN_SCORE - u8::MAX as i32;
+
+/// Bound on all scores, used as the initial search window. Unlike `i32::MIN`, can be safely
+/// negated.
+const INFINITY: i32 = WIN_SCORE + 1;
+
+/// Half-width of the aspiration window searched around the previous iteration's score.
+const ASPIRATION_WINDOW: i32 = 50;
+
+/// Number of plies by which the search following a null move is reduced.
+const NULL_MOVE_REDUCTION: u8 = 2;
+
+/// Deepest iteration the search will start. A search to this depth reaches one ply further at its
+/// leaves, which must still fit in a `u8`.
+const MAX_SEARCH_DEPTH: u8 = u8::MAX - 1;
+
+/// Null-move pruning is not tried when at least this many of the king's neighbours are hostile.
+const NULL_MOVE_KING_GUARD: usize = 2;
+
+/// Late move reductions only apply to plays at least this far down the ordering...
+const LMR_MIN_PLAYS: usize = 3;
+
+/// ...at nodes with at least this much depth remaining.
+const LMR_MIN_DEPTH: u8 = 3;
+
+/// Time limit for [`BasicAi::ponder`], which is effectively unlimited.
+const MAX_PONDER_TIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Whether `score` represents a forced win for one side or the other.
+fn is_win_score(score: i32) -> bool {
+    score.abs() >= WIN_THRESHOLD
+}
+
+/// Convert a win score from the perspective of the root to one relative to the node at `ply`, for
+/// storage in the transposition table. The same position can be reached at different plies, so
+/// the table stores the distance to the win from the position itself.
+fn score_to_tt(score: i32, ply: u8) -> i32 {
+    if score >= WIN_THRESHOLD {
+        score + ply as i32
+    } else if score <= -WIN_THRESHOLD {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+/// The inverse of [`score_to_tt`].
+fn score_from_tt(score: i32, ply: u8) -> i32 {
+    if score >= WIN_THRESHOLD {
+        score - ply as i32
+    } else if score <= -WIN_THRESHOLD {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+/// All legal plays for the side to play in `state`.
+pub fn legal_plays<T: BoardState>(logic: &GameLogic<T>, state: &GameState<T>) -> Vec<ValidPlay> {
+    let mut plays = Vec::new();
+    for t in state.board.occupied_by_side(state.side_to_play) {
+        for p in logic.iter_plays(t, state).expect("Could not iterate plays") {
+            plays.push(p);
+        }
+    }
+    plays
+}
+
+/// The side opposing `side`.
+fn opponent(side: pieces::Side) -> pieces::Side {
+    if side == Attacker { Defender } else { Attacker }
+}
+
+/// `1` for the attacker and `-1` for the defender. Multiplying a score from the attacker's
+/// perspective by this gives the score from the given side's perspective, and vice versa.
+fn side_sign(side: pieces::Side) -> i32 {
+    if side == Attacker { 1 } else { -1 }
+}
+
+/// Whether a search should be abandoned, either because it has been cancelled or because it has
+/// run out of time.
+fn should_stop(cancel: &CancelToken, cutoff_time: Instant) -> bool {
+    cancel.is_cancelled() || Instant::now() > cutoff_time
+}
+
+/// Seed from which [`ZobristTable`] bitstrings are generated. Changing this invalidates saved
+/// transposition tables.
+const ZOBRIST_SEED: u64 = 0x7af1_2d5e_9c3b_4a61;
+
+/// A simple pseudo-random number generator (SplitMix64), used to generate [`ZobristTable`]
+/// bitstrings. Unlike the generators in `rand`, its output is guaranteed never to change.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct ZobristTable {
+    /// Bitstrings representing piece placement
+    piece_bits: Vec<[u64; 3]>,
+    /// Bitstring to use used when it's the defender's move.
+    def_to_move_bits: u64,
+    board_len: u8
+}
+
+impl ZobristTable {
+
+    /// Create the table for boards of the given size. The bitstrings are pseudo-random, but the
+    /// same for every table of the same size, so that hashes (and so transposition table entries)
+    /// remain valid between sessions.
+    fn new(board_len: u8) -> Self {
+        let mut rng = SplitMix64(ZOBRIST_SEED ^ board_len as u64);
+        let n_tiles = (board_len as usize).pow(2);
+        let mut hashes: Vec<[u64; 3]> = Vec::with_capacity(n_tiles);
+        for _ in 0..n_tiles {
+            hashes.push([rng.next_u64(), rng.next_u64(), rng.next_u64()]);
+        }
+        Self {
+            piece_bits: hashes,
+            def_to_move_bits: rng.next_u64(),
+            board_len
+        }
+    }
+
+    fn piece_index(piece: Piece) -> usize {
+        if piece.side == Attacker { 0 } else if piece.piece_type == Soldier { 1 } else { 2 }
+    }
+
+    /// Bitstring representing `piece` being on `tile`.
+    fn tile_bits(&self, tile: Tile, piece: Piece) -> u64 {
+        let bi = tile.col as usize + (tile.row as usize * self.board_len as usize);
+        self.piece_bits[bi][Self::piece_index(piece)]
+    }
+
+    /// Compute the hash of the given position from scratch.
+    fn hash<T: BoardState>(&self, board_state: T, side_to_play: pieces::Side) -> u64 {
+        let mut h = 0u64;
+        if side_to_play == Defender {
+            h ^= self.def_to_move_bits;
+        }
+        for s in [Attacker, Defender] {
+            for t in board_state.occupied_by_side(s) {
+                let p = board_state.get_piece(t).expect("There should be a piece here.");
+                h ^= self.tile_bits(t, p);
+            }
+        }
+        h
+    }
+}
+
+pub trait Ai {
+    type BoardState: BoardState;
+
+    fn next_play(
+        &mut self,
+        game_state: &GameState<Self::BoardState>,
+        posn_history: &[Position<Self::BoardState>],
+        time_to_play: Duration,
+        cancel: &CancelToken,
+        on_progress: &mut dyn FnMut(&SearchProgress)
+    ) -> Result<(ValidPlay, SearchInfo), AiError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicAi<T: BoardState> {
+    logic: GameLogic<T>,
+    zt: ZobristTable,
+    tt: TranspositionTable,
+    /// Weights used to evaluate positions. Defaults to [`EvalParams::default`]; set this to the
+    /// parameters of the variant being played.
+    pub eval_params: EvalParams,
+    /// Maximum number of plies the quiescence search may extend beyond the main search.
+    pub quiescence_depth: u8,
+    /// If set, the search stops after completing this depth, even if it has time to search deeper.
+    pub max_depth: Option<u8>,
+    /// If set, the search doesn't start a new iteration once it has visited this many nodes.
+    pub node_limit: Option<u64>,
+    /// Maximum amount of random noise added to (or subtracted from) the evaluation of each
+    /// position, to make the AI weaker. Zero for no noise. If non-zero, the search doesn't store
+    /// its results in the transposition table, which may be shared with stronger AIs.
+    pub eval_noise: i32,
+    /// Probability (between 0 and 1) of making a random legal play instead of the best play found,
+    /// to make the AI weaker.
+    pub blunder_chance: f64,
+    /// Number of threads to search with (see [`BasicAi::next_play`]). Ignored on WASM, where the
+    /// search is always single-threaded.
+    pub threads: usize,
+    /// Number of plies by which this AI's iterative deepening is ahead of the main thread's, if it
+    /// is a helper thread in a parallel search.
+    depth_offset: u8,
+    /// Whether to use null-move pruning.
+    pub null_move_pruning: bool,
+    /// Whether to use late move reductions.
+    pub late_move_reductions: bool,
+    /// If set, plays from this book are made in the positions it covers, instead of searching
+    /// (unless [`BasicAi::eval_noise`] or [`BasicAi::blunder_chance`] is set).
+    pub opening_book: Option<Arc<OpeningBook>>,
+    /// If set, positions it covers are scored exactly instead of being searched, and plays in
+    /// them are taken from it.
+    pub tablebase: Option<Arc<Tablebase>>,
+    /// Quiet plays which recently caused a beta cutoff, indexed by ply. These are likely to cause
+    /// a cutoff in sibling nodes too, so are tried early.
+    killers: Vec<[Option<ValidPlay>; 2]>,
+    /// Scores for quiet plays which have caused beta cutoffs anywhere in the tree, indexed by
+    /// [`BasicAi::history_index`].
+    history: Vec<u32>
+}
+
+/// Default size of the transposition table, in megabytes. Smaller on WASM, where memory is more
+/// constrained.
+#[cfg(target_arch = "wasm32")]
+pub const DEFAULT_TT_SIZE_MB: usize = 128;
+#[cfg(not(target_arch = "wasm32"))]
+pub const DEFAULT_TT_SIZE_MB: usize = 512;
+
+/// A sensible number of threads for [`BasicAi::threads`] on this machine: the number of cores
+/// available, or 1 on WASM.
+#[cfg(target_arch = "wasm32")]
+pub fn default_search_threads() -> usize {
+    1
+}
+#[cfg(not(target_arch = "wasm32"))]
+pub fn default_search_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Default value for [`BasicAi::quiescence_depth`].
+const DEFAULT_QUIESCENCE_DEPTH: u8 = 4;
+
+impl<T: BoardState> BasicAi<T> {
+    
+    pub fn new(logic: GameLogic<T>) -> Self {
+        Self::with_tt_size(logic, DEFAULT_TT_SIZE_MB)
+    }
+
+    /// Create a new AI whose transposition table uses (approximately) the given number of
+    /// megabytes.
+    pub fn with_tt_size(logic: GameLogic<T>, tt_size_mb: usize) -> Self {
+        Self {
+            logic,
+            zt: ZobristTable::new(logic.board_geo.side_len),
+            tt: TranspositionTable::new(tt_size_mb),
+            eval_params: EvalParams::default(),
+            quiescence_depth: DEFAULT_QUIESCENCE_DEPTH,
+            max_depth: None,
+            threads: 1,
+            depth_offset: 0,
+            node_limit: None,
+            eval_noise: 0,
+            blunder_chance: 0.0,
+            null_move_pruning: true,
+            late_move_reductions: true,
+            opening_book: None,
+            tablebase: None,
+            killers: Vec::new(),
+            history: vec![0; (logic.board_geo.side_len as usize).pow(4) * 2]
+        }
+    }
+
+    /// Resize the transposition table to use (approximately) the given number of megabytes. This
+    /// clears the table.
+    pub fn set_tt_size(&mut self, tt_size_mb: usize) {
+        self.tt = TranspositionTable::new(tt_size_mb);
+    }
+
+    /// Index of the given play, by the given side, in the history table.
+    fn history_index(&self, play: ValidPlay, side: pieces::Side) -> usize {
+        let side_len = self.logic.board_geo.side_len as usize;
+        let n_tiles = side_len.pow(2);
+        let from = play.play.from;
+        let to = play.play.to();
+        let from_i = from.col as usize + (from.row as usize * side_len);
+        let to_i = to.col as usize + (to.row as usize * side_len);
+        let side_i = if side == Attacker { 0 } else { 1 };
+        (((from_i * n_tiles) + to_i) * 2) + side_i
+    }
+
+    /// Compute the hash of `new_state`, which was reached by making `play` in `state` (whose hash
+    /// is `hash`), by applying only the changes made by the play rather than hashing the whole
+    /// board. In debug builds, the result is checked against a full recomputation.
+    fn hash_after(
+        &self,
+        hash: u64,
+        play: ValidPlay,
+        state: &GameState<T>,
+        new_state: &GameState<T>
+    ) -> u64 {
+        let board = &state.board;
+        let piece = board.get_piece(play.play.from).expect("No piece to move.");
+        let mut h = hash;
+        h ^= self.zt.tile_bits(play.play.from, piece);
+        h ^= self.zt.tile_bits(play.play.to(), piece);
+        for t in self.logic.get_captures(play, piece, state).occupied() {
+            let captured = board.get_piece(t).expect("No piece to capture.");
+            h ^= self.zt.tile_bits(t, captured);
+        }
+        if new_state.side_to_play != state.side_to_play {
+            h ^= self.zt.def_to_move_bits;
+        }
+        debug_assert_eq!(
+            h,
+            self.zt.hash(new_state.board, new_state.side_to_play),
+            "Incremental hash does not match full hash after {play}"
+        );
+        h
+    }
+
+    /// Whether it is safe to try null-move pruning in `state`. Null-move pruning assumes that
+    /// passing is never better than making a play, which is false when every available play
+    /// worsens the position (zugzwang). In tafl this is most likely when the king is nearly
+    /// enclosed, so we don't try it then.
+    fn null_move_safe(&self, state: &GameState<T>) -> bool {
+        let Some(king_tile) = state.board.get_king() else {
+            return false
+        };
+        let hostile_neighbors = self.logic.board_geo.neighbors(king_tile).iter()
+            .filter(|n| self.logic.tile_hostile(**n, Piece::new(King, Defender), &state.board))
+            .count();
+        hostile_neighbors < NULL_MOVE_KING_GUARD
+    }
+
+    /// Record that `play` caused a beta cutoff at the given ply, so that it is tried earlier in
+    /// similar positions. Captures are already tried early, so are not recorded.
+    fn record_cutoff(&mut self, play: ValidPlay, state: &GameState<T>, depth: u8, ply: u8) {
+        let moving_piece = state.board.get_piece(play.play.from).expect("No piece to move.");
+        if self.logic.get_captures(play, moving_piece, state).occupied().next().is_some() {
+            return
+        }
+        let ply = ply as usize;
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let slots = &mut self.killers[ply];
+        if slots[0] != Some(play) {
+            slots[1] = slots[0];
+            slots[0] = Some(play);
+        }
+        let i = self.history_index(play, state.side_to_play);
+        // Cutoffs closer to the root are more valuable
+        self.history[i] = self.history[i].saturating_add((depth as u32).pow(2));
+    }
+
+    /// Prepare the move ordering heuristics for a new search. Killers are specific to the previous
+    /// position so are discarded, while history scores are aged so that they don't swamp newer
+    /// information.
+    fn age_heuristics(&mut self) {
+        self.killers.clear();
+        for h in self.history.iter_mut() {
+            *h /= 2;
+        }
+    }
+    
+    /// The corner tiles of the board.
+    fn corners(&self) -> [Tile; 4] {
+        let max = self.logic.board_geo.side_len - 1;
+        [Tile::new(0, 0), Tile::new(0, max), Tile::new(max, 0), Tile::new(max, max)]
+    }
+
+    /// Whether every tile strictly between `from` and `to`, and `to` itself, is empty. `from` and
+    /// `to` must be in the same row or column.
+    fn line_is_open(&self, from: Tile, to: Tile, board: &T) -> bool {
+        let row_step = (to.row as i16 - from.row as i16).signum();
+        let col_step = (to.col as i16 - from.col as i16).signum();
+        let (mut row, mut col) = (from.row as i16, from.col as i16);
+        while (row, col) != (to.row as i16, to.col as i16) {
+            row += row_step;
+            col += col_step;
+            if board.get_piece(Tile::new(row as u8, col as u8)).is_some() {
+                return false
+            }
+        }
+        true
+    }
+
+    /// Number of escape tiles the king at `king_tile` could reach in a single play: corners in a
+    /// corner escape game, or edge tiles (at most one in each direction) in an edge escape game.
+    fn count_escape_routes(&self, king_tile: Tile, board: &T) -> usize {
+        let max = self.logic.board_geo.side_len - 1;
+        let targets = if self.logic.rules.edge_escape {
+            [
+                Tile::new(0, king_tile.col),
+                Tile::new(max, king_tile.col),
+                Tile::new(king_tile.row, 0),
+                Tile::new(king_tile.row, max)
+            ]
+        } else {
+            self.corners()
+        };
+        targets.into_iter()
+            .filter(|t| *t != king_tile && (t.row == king_tile.row || t.col == king_tile.col))
+            .filter(|t| self.line_is_open(king_tile, *t, board))
+            .count()
+    }
+
+    /// Evaluate board state and return a score. Higher = better for attacker, lower = better for
+    /// defender.
+    fn eval_board(&self, board: &T) -> i32 {
+        let king_tile_opt = board.get_king();
+        if king_tile_opt.is_none() {
+            // No king, so presumably it was captured
+            return WIN_SCORE
+        }
+        let king_tile = king_tile_opt.unwrap(); // unwrap safe because we just checked
+        let king_coords = Coords::from(king_tile);
+
+        let params = &self.eval_params;
+        let mut score = 0i32;
+        let att_count = board.count_pieces_of_side(Attacker) as i32;
+        let def_count = board.count_pieces_of_side(Defender) as i32;
+
+        // More pieces a side has/fewer pieces the other side has = better for that side
+        score += att_count * params.attacker_piece;
+        score -= (def_count - 1) * params.defender_piece;
+
+        // More pieces on the board generally = better for attacker
+        score += (att_count + def_count) * params.piece_total;
+
+        // King closer to edge = better for defender
+        let side_len = self.logic.board_geo.side_len;
+        let col_dist = min(king_tile.col, side_len - king_tile.col - 1);
+        let row_dist = min(king_tile.row, side_len - king_tile.row - 1);
+        score += col_dist as i32 * params.king_edge_dist;
+        score += row_dist as i32 * params.king_edge_dist;
+
+        // Fewer hostile pieces near king = better for defender
+        score += self.logic.board_geo.neighbors(king_tile).iter()
+            .filter(|n| self.logic.tile_hostile(**n, Piece::new(King, Defender), board))
+            .count() as i32 * params.king_hostile_neighbor;
+
+        // Open lines from the king to escape tiles = better for defender. The attacker can only
+        // block one route per play, so two or more open routes is almost certainly a win.
+        let escape_routes = self.count_escape_routes(king_tile, board);
+        if escape_routes >= 2 {
+            score -= params.double_escape_route;
+        } else {
+            score -= escape_routes as i32 * params.escape_route;
+        }
+
+        // Attackers guarding the tiles next to the corners = better for attacker
+        if !self.logic.rules.edge_escape {
+            for corner in self.corners() {
+                score += self.logic.board_geo.neighbors(corner).iter()
+                    .filter(|n| board.get_piece(**n).is_some_and(|p| p.side == Attacker))
+                    .count() as i32 * params.corner_blockade;
+            }
+        }
+
+        // Attacker pieces closer to king = better for attacker
+        let mut total_dist = 0u32;
+        let mut attacker_count = 0u32;
+        for tile in board.occupied_by_side(Attacker) {
+            total_dist += Coords::from(tile).row_col_offset_from(king_coords)
+                .manhattan_dist() as u32;
+            attacker_count += 1;
+        }
+        score -= (total_dist / attacker_count) as i32 * params.attacker_king_dist;
+        
+        score
+    }
+    
+    /// Statically evaluate `state`, without searching. Higher = better for attacker, lower =
+    /// better for defender.
+    pub fn evaluate(&self, state: &GameState<T>) -> i32 {
+        self.eval_state(state, 0)
+    }
+
+    /// Evaluate game state (board state + repetitions) and return a score. Higher = better for
+    /// attacker, lower = better for defender. `ply` is the distance of `state` from the root of
+    /// the search, which is subtracted from the score of a win so that quicker wins score higher.
+    fn eval_state(&self, state: &GameState<T>, ply: u8) -> i32 {
+        if let Over(Win(_, winner)) = state.status {
+            return if winner == Attacker {
+                WIN_SCORE - ply as i32
+            } else {
+                -WIN_SCORE + ply as i32
+            }
+        } else if let Over(Draw(_)) = state.status {
+            return 0
+        }
+
+        let mut score = self.eval_board(&state.board);
+        if self.eval_noise > 0 {
+            score += thread_rng().gen_range(-self.eval_noise..=self.eval_noise);
+        }
+
+        score
+    }
+    
+    /// Quickly evaluate a play. Used in play ordering. Returns the number of pieces captured by the
+    /// play, and a score for the play (which takes the captures into account).
+    fn eval_play(&self, play: ValidPlay, state: &GameState<T>) -> (usize, i32) {
+        let mut score = 0i32;
+        let to = play.play.to();
+        let board = &state.board;
+        let moving_piece = board.get_piece(play.play.from).expect("No piece to move.");
+
+        // Prioritise capture plays
+        let n_captures = self.logic.get_captures(play, moving_piece, state).occupied().count();
+        score += (n_captures as i32) * 1000;
+        
+        // King-specific plays
+        if moving_piece == KING {
+            // Bonus for king moves towards edges (escape routes)
+            let to_edge_dist = min(
+                min(to.row, self.logic.board_geo.side_len - 1 - to.row),
+                min(to.col, self.logic.board_geo.side_len - 1 - to.col)
+            );
+            score += (4 - to_edge_dist as i32) * 300;
+
+            // Penalty for moving king next to attackers
+            let hostile_neighbors = self.logic.board_geo.neighbors(to)
+                .iter()
+                .filter(|pos| {
+                    board.get_piece(**pos)
+                        .map_or(false, |p| p.side == Attacker)
+                })
+                .count();
+            score -= (hostile_neighbors as i32) * 400;
+        }
+
+        // 3. Mobility scoring
+        let mobility = self.logic.board_geo.neighbors(to)
+            .iter()
+            .filter(|pos| board.get_piece(**pos).is_none())
+            .count();
+        score += (mobility as i32) * 50;
+        
+        (n_captures, score)
+    }
+
+    /// Sort plays so that those most likely to be good are searched first. The order is: the
+    /// play from the transposition table, then captures, then killer plays, then other ("quiet")
+    /// plays by history score. Returns each play along with whether it is quiet.
+    fn order_plays(
+        &self,
+        plays: Vec<ValidPlay>,
+        state: &GameState<T>,
+        tt_play: Option<ValidPlay>,
+        killers: [Option<ValidPlay>; 2]
+    ) -> Vec<(ValidPlay, bool)> {
+        let mut scored_plays: Vec<(ValidPlay, (u8, i32, i32))> = plays.into_iter()
+            .map(|p| {
+                let (n_captures, score) = self.eval_play(p, state);
+                let key = if tt_play == Some(p) {
+                    (3, 0, 0)
+                } else if n_captures > 0 {
+                    (2, score, 0)
+                } else if let Some(slot) = killers.iter().position(|k| *k == Some(p)) {
+                    (1, -(slot as i32), 0)
+                } else {
+                    let history = self.history[self.history_index(p, state.side_to_play)];
+                    (0, history.min(i32::MAX as u32) as i32, score)
+                };
+                (p, key)
+            })
+            .collect();
+
+        scored_plays.sort_unstable_by(|a, b| b.1.cmp(&a.1));
+        scored_plays.into_iter().map(|(p, key)| (p, key.0 == 0)).collect()
+    }
+
+    /// Plays which are likely to change the evaluation of the position significantly, ie,
+    /// captures and king moves to the edge of the board. Used in quiescence search.
+    fn noisy_plays(&self, state: &GameState<T>) -> Vec<ValidPlay> {
+        let side_len = self.logic.board_geo.side_len;
+        let mut plays = Vec::new();
+        for t in state.board.occupied_by_side(state.side_to_play) {
+            let piece = state.board.get_piece(t).expect("There should be a piece here.");
+            for p in self.logic.iter_plays(t, state).expect("Could not iterate plays") {
+                let to = p.play.to();
+                let king_to_edge = piece == KING
+                    && (to.row == 0 || to.col == 0 || to.row == side_len - 1 || to.col == side_len - 1);
+                if king_to_edge || self.logic.get_captures(p, piece, state).occupied().next().is_some() {
+                    plays.push(p);
+                }
+            }
+        }
+        plays
+    }
+
+    /// Quiescence search. Called at the leaf nodes of the main search to keep searching noisy
+    /// plays (see [`BasicAi::noisy_plays`]) until the position is quiet or `depth` reaches zero,
+    /// so that we don't evaluate a position where a capture or escape is imminent as if it were
+    /// stable. Returns the score of `state` from the perspective of the side to play.
+    fn quiesce(
+        &mut self,
+        state: GameState<T>,
+        depth: u8,
+        ply: u8,
+        mut alpha: i32,
+        beta: i32,
+        posn_history: &mut Vec<Position<T>>,
+        stats: &mut SearchStats,
+        cancel: &CancelToken,
+        cutoff_time: Instant
+    ) -> i32 {
+        if should_stop(cancel, cutoff_time) {
+            return 0;
+        }
+        stats.q_states += 1;
+
+        // The side to play can always choose a quiet play instead, so the static evaluation is a
+        // lower bound on the score ("standing pat").
+        let stand_pat = side_sign(state.side_to_play) * self.eval_state(&state, ply);
+        if depth == 0 || state.status != Ongoing {
+            return stand_pat;
+        }
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let plays = self.noisy_plays(&state);
+        if plays.is_empty() {
+            return stand_pat;
+        }
+        let plays = self.order_plays(plays, &state, None, [None; 2]);
+
+        let mut best_score = stand_pat;
+        posn_history.push((&state).into());
+        for (p, _) in plays {
+            let next_state = self.logic.do_valid_play(p, state, Some(posn_history)).new_state;
+            let score = -self.quiesce(
+                next_state,
+                depth - 1,
+                ply.saturating_add(1),
+                -beta,
+                -alpha,
+                posn_history,
+                stats,
+                cancel,
+                cutoff_time
+            );
+            if should_stop(cancel, cutoff_time) {
+                break
+            }
+            best_score = best_score.max(score);
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                stats.ab_prunes += 1;
+                break
+            }
+        }
+        posn_history.pop();
+
+        best_score
+    }
+
+    /// Find the legal play in `state` from `from` to `to`, if any. Used to recover plays stored in
+    /// the transposition table, which also guards against illegal plays retrieved following a
+    /// hash collision.
+    fn resolve_play(&self, from: Tile, to: Tile, state: &GameState<T>) -> Option<ValidPlay> {
+        self.logic.iter_plays(from, state).ok()?.find(|p| p.play.to() == to)
+    }
+
+    /// The best play stored in the transposition table for `state` (whose hash is `hash`), if any.
+    fn tt_play(&self, hash: u64, state: &GameState<T>) -> Option<ValidPlay> {
+        self.tt.probe(hash)
+            .and_then(|e| e.best_play_tiles())
+            .and_then(|(from, to)| self.resolve_play(from, to, state))
+    }
+
+    /// Reconstruct the principal variation beginning with `first_play` from `state`, by following
+    /// the best plays stored in the transposition table. The returned line will contain at most
+    /// `max_len` plays, and may be shorter if the relevant entries have been overwritten.
+    fn extract_pv(&self, first_play: ValidPlay, state: GameState<T>, max_len: usize) -> Vec<ValidPlay> {
+        let mut pv = vec![first_play];
+        let mut state = self.logic.do_valid_play(first_play, state, None).new_state;
+        let mut seen = HashSet::new();
+        while pv.len() < max_len && state.status == Ongoing {
+            let hash = self.zt.hash(state.board, state.side_to_play);
+            if !seen.insert(hash) {
+                // Repeated position, so following the table any further would loop forever
+                break
+            }
+            let Some(play) = self.tt_play(hash, &state) else {
+                break
+            };
+            pv.push(play);
+            state = self.logic.do_valid_play(play, state, None).new_state;
+        }
+        pv
+    }
+
+    /// Negamax search with alpha-beta pruning, using principal variation search: the first
+    /// (hopefully best) play at each node is searched with the full window, and the remaining
+    /// plays are searched with a null window, just to prove that they are no better. A play that
+    /// turns out to be better is re-searched with the full window.
+    ///
+    /// `hash` is the Zobrist hash of `state`, which is passed down from the parent node and
+    /// updated incrementally (see [`BasicAi::hash_after`]) rather than recomputed at every node.
+    /// `alpha`, `beta` and the returned score are from the perspective of the side to play in
+    /// `state`. `allow_null` should be `false` if the play leading to `state` was a null move, so
+    /// that we never make two in a row. Returns (best score, best play) tuple.
+    ///
+    /// If the search is cancelled or runs out of time, the returned score is meaningless and
+    /// nothing is stored in the transposition table, so the caller must check [`should_stop`]
+    /// before using the result.
+    pub(crate) fn negamax(
+        &mut self,
+        state: GameState<T>,
+        hash: u64,
+        depth: u8,
+        ply: u8,
+        mut alpha: i32,
+        beta: i32,
+        allow_null: bool,
+        posn_history: &mut Vec<Position<T>>,
+        stats: &mut SearchStats,
+        cancel: &CancelToken,
+        cutoff_time: Instant
+    ) -> (i32, Option<ValidPlay>) {
+        if should_stop(cancel, cutoff_time) {
+            return (0, None);
+        }
+        stats.states += 1;
+        
+        if let Some(tt_entry) = self.tt.probe(hash) {
+            // Found entry in transposition table
+            if tt_entry.depth >= depth {
+                stats.tt_hits += 1;
+                let score = score_from_tt(tt_entry.score, ply);
+                match tt_entry.node_type() {
+                    NodeType::Exact => return (score, None),
+                    NodeType::LowerBound if score >= beta => return (score, None),
+                    NodeType::UpperBound if score <= alpha => return (score, None),
+                    _ => {}
+                }
+            }
+        }
+        
+        if state.status == Ongoing {
+            if let Some(score) = self.tablebase_score(&state, ply) {
+                stats.tb_hits += 1;
+                return (score, None);
+            }
+        }
+
+        if depth == 0 || state.status != Ongoing {
+            // Leaf node
+            stats.paths += 1;
+            if state.status != Ongoing {
+                return (side_sign(state.side_to_play) * self.eval_state(&state, ply), None);
+            }
+            let score = self.quiesce(
+                state,
+                self.quiescence_depth,
+                ply,
+                alpha,
+                beta,
+                posn_history,
+                stats,
+                cancel,
+                cutoff_time
+            );
+            return (score, None);
+        }
+
+        // A null window means this node is not on the principal variation, so we can afford to
+        // search it less thoroughly.
+        let is_pv = beta - alpha > 1;
+
+        posn_history.push((&state).into());
+
+        // Null-move pruning: if we pass and a reduced search still shows the opponent can't bring
+        // the score below beta, an actual play almost certainly won't either.
+        if self.null_move_pruning
+            && allow_null
+            && !is_pv
+            && depth > NULL_MOVE_REDUCTION
+            && self.null_move_safe(&state)
+            && side_sign(state.side_to_play) * self.eval_state(&state, ply) >= beta {
+            let mut null_state = state;
+            null_state.side_to_play = opponent(state.side_to_play);
+            let score = -self.negamax(
+                null_state, hash ^ self.zt.def_to_move_bits, depth - 1 - NULL_MOVE_REDUCTION,
+                ply + 1, -beta, -beta + 1, false, posn_history, stats, cancel, cutoff_time
+            ).0;
+            if should_stop(cancel, cutoff_time) {
+                posn_history.pop();
+                return (0, None);
+            }
+            if score >= beta {
+                stats.null_move_prunes += 1;
+                posn_history.pop();
+                // A win found after passing isn't proven, as passing isn't actually legal.
+                return (if is_win_score(score) { beta } else { score }, None);
+            }
+        }
+        
+        let alpha_orig = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_play: Option<ValidPlay> = None;
+        
+        // Collect and sort moves
+        let plays = legal_plays(&self.logic, &state);
+
+        let tt_play = self.tt_play(hash, &state);
+        let killers = self.killers.get(ply as usize).copied().unwrap_or_default();
+        let plays = self.order_plays(plays, &state, tt_play, killers);
+
+        for (i, (p, quiet)) in plays.into_iter().enumerate() {
+            let child = self.logic.do_valid_play(p, state, Some(posn_history)).new_state;
+            let child_hash = self.hash_after(hash, p, &state, &child);
+            let score = if i == 0 {
+                -self.negamax(
+                    child, child_hash, depth - 1, ply + 1, -beta, -alpha, true, posn_history, stats,
+                    cancel, cutoff_time
+                ).0
+            } else {
+                // Late move reductions: quiet plays late in the ordering are unlikely to be good,
+                // so first search them to a reduced depth, and only search them properly if that
+                // suggests they might beat alpha.
+                let reduce = self.late_move_reductions
+                    && quiet
+                    && i >= LMR_MIN_PLAYS
+                    && depth >= LMR_MIN_DEPTH;
+                let mut score = alpha + 1;
+                if reduce {
+                    stats.lmr_reductions += 1;
+                    score = -self.negamax(
+                        child, child_hash, depth - 2, ply + 1, -alpha - 1, -alpha, true,
+                        posn_history, stats, cancel, cutoff_time
+                    ).0;
+                }
+                if score > alpha && !should_stop(cancel, cutoff_time) {
+                    score = -self.negamax(
+                        child, child_hash, depth - 1, ply + 1, -alpha - 1, -alpha, true,
+                        posn_history, stats, cancel, cutoff_time
+                    ).0;
+                }
+                if score > alpha && score < beta && !should_stop(cancel, cutoff_time) {
+                    stats.pvs_researches += 1;
+                    score = -self.negamax(
+                        child, child_hash, depth - 1, ply + 1, -beta, -alpha, true, posn_history,
+                        stats, cancel, cutoff_time
+                    ).0;
+                }
+                score
+            };
+            if should_stop(cancel, cutoff_time) {
+                break
+            }
+            if score > best_score {
+                best_score = score;
+                best_play = Some(p);
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                stats.ab_prunes += 1;
+                self.record_cutoff(p, &state, depth, ply);
+                break
+            }
+        }
+        
+        posn_history.pop();
+
+        if should_stop(cancel, cutoff_time) {
+            // Search was interrupted, so we can't trust the result.
+            return (0, None);
+        }
+
+        // Store in transposition table. The search is fail-soft, so a score outside the original
+        // window is a bound on the true score in the direction it fell outside. Scores found with
+        // evaluation noise aren't stored, as the table is shared with AIs playing at other levels
+        // (and with pondering), which would otherwise play the noisy results.
+        if self.eval_noise == 0 {
+            let node_type = if best_score <= alpha_orig {
+                NodeType::UpperBound
+            } else if best_score >= beta {
+                NodeType::LowerBound
+            } else {
+                NodeType::Exact
+            };
+            self.tt.insert(hash, depth, score_to_tt(best_score, ply), node_type, best_play, stats);
+        }
+        
+        (best_score, best_play)
+    }
+
+    /// Search the root position to the given depth within the window (`alpha`, `beta`), which is
+    /// from the perspective of the side to play. `prev_best` is the best play found by the
+    /// previous iteration, if any, which is searched first. Returns the best play, its score and
+    /// whether the search was interrupted.
+    ///
+    /// If every play fails low (scores `alpha` or less) or one fails high (scores `beta` or more),
+    /// the returned score is only a bound, and the caller should search again with a wider window.
+    fn search_to_depth(
+        &mut self,
+        depth: u8,
+        state: GameState<T>,
+        mut alpha: i32,
+        beta: i32,
+        prev_best: Option<ValidPlay>,
+        stats: &mut SearchStats,
+        posn_history: &mut Vec<Position<T>>,
+        cancel: &CancelToken,
+        cutoff_time: Instant,
+        on_root_play: &mut dyn FnMut(&SearchStats)
+    ) -> (Option<ValidPlay>, i32, bool) {
+        
+        let plays = legal_plays(&self.logic, &state);
+        let plays = self.order_plays(plays, &state, prev_best, [None; 2]);
+        let root_hash = self.zt.hash(state.board, state.side_to_play);
+
+        let mut best_score = -INFINITY;
+        let mut best_play: Option<ValidPlay> = None;
+
+        // `posn_history` already includes the root position, so unlike `negamax` we don't push it
+        for (i, (play, _)) in plays.into_iter().enumerate() {
+            if should_stop(cancel, cutoff_time) {
+                break
+            }
+            let child = self.logic.do_valid_play(play, state, Some(posn_history)).new_state;
+            let child_hash = self.hash_after(root_hash, play, &state, &child);
+            let score = if i == 0 {
+                -self.negamax(
+                    child, child_hash, depth, 1, -beta, -alpha, true, posn_history, stats, cancel,
+                    cutoff_time
+                ).0
+            } else {
+                let mut score = -self.negamax(
+                    child, child_hash, depth, 1, -alpha - 1, -alpha, true, posn_history, stats,
+                    cancel, cutoff_time
+                ).0;
+                if score > alpha && score < beta && !should_stop(cancel, cutoff_time) {
+                    stats.pvs_researches += 1;
+                    score = -self.negamax(
+                        child, child_hash, depth, 1, -beta, -alpha, true, posn_history, stats,
+                        cancel, cutoff_time
+                    ).0;
+                }
+                score
+            };
+            if should_stop(cancel, cutoff_time) {
+                break
+            }
+            if score > best_score {
+                best_score = score;
+                best_play = Some(play);
+            }
+            alpha = alpha.max(score);
+            on_root_play(stats);
+            if alpha >= beta {
+                break
+            }
+        }
+
+        (best_play, best_score, should_stop(cancel, cutoff_time))
+    }
+
+    fn iddfs(
+        &mut self,
+        state: GameState<T>,
+        stats: &mut SearchStats,
+        posn_history: &mut Vec<Position<T>>,
+        time_to_play: Duration,
+        cancel: &CancelToken,
+        on_progress: &mut dyn FnMut(&SearchProgress),
+        info: &mut SearchInfo
+    ) -> (Option<ValidPlay>, i32) {
+        self.age_heuristics();
+        // Root scores are from the perspective of the side to play; reported scores are from the
+        // attacker's perspective.
+        let sign = side_sign(state.side_to_play);
+        let mut depth = 1 + self.depth_offset;
+        let mut best_play: Option<ValidPlay> = None;
+        let mut best_score: i32 = -INFINITY;
+        let start_time = Instant::now();
+        loop {
+            // Use a narrow window around the previous iteration's score, as the score is unlikely
+            // to change much, and a narrow window allows more pruning.
+            let (mut alpha, mut beta) = if best_play.is_some() && !is_win_score(best_score) {
+                (best_score - ASPIRATION_WINDOW, best_score + ASPIRATION_WINDOW)
+            } else {
+                (-INFINITY, INFINITY)
+            };
+            let (play, score, out_of_time) = loop {
+                let best = best_play.map(|p| (p, sign * best_score));
+                let mut on_root_play = |stats: &SearchStats| on_progress(&SearchProgress {
+                    depth,
+                    nodes: stats.states,
+                    elapsed: start_time.elapsed(),
+                    best
+                });
+                let (play, score, out_of_time) = self.search_to_depth(
+                    depth,
+                    state,
+                    alpha,
+                    beta,
+                    best_play,
+                    stats,
+                    posn_history,
+                    cancel,
+                    start_time + time_to_play,
+                    &mut on_root_play
+                );
+                // If the score fell outside the window, we only know a bound, so search again
+                // with the window opened up on that side.
+                if !out_of_time && score <= alpha && alpha > -INFINITY {
+                    stats.aspiration_researches += 1;
+                    alpha = -INFINITY;
+                } else if !out_of_time && score >= beta && beta < INFINITY {
+                    stats.aspiration_researches += 1;
+                    beta = INFINITY;
+                } else {
+                    break (play, score, out_of_time);
+                }
+            };
+            if let Some(p) = play {
+                if !out_of_time {
+                    best_play = play;
+                    best_score = score;
+                    info.depths.push(DepthInfo {
+                        depth,
+                        score: sign * score,
+                        pv: self.extract_pv(p, state, depth as usize + 1),
+                        nodes: stats.states,
+                        elapsed: start_time.elapsed()
+                    });
+                }
+            } 
+            // Once we have found a forced win, searching deeper can't find a quicker one, and once
+            // every play is shown to lose, it can't find a way out.
+            let proven = !out_of_time && is_win_score(score);
+            let reached_max = self.max_depth.is_some_and(|d| depth >= d)
+                || depth >= MAX_SEARCH_DEPTH
+                || self.node_limit.is_some_and(|n| stats.states >= n);
+            if out_of_time || play.is_none() || proven || reached_max {
+                if out_of_time {
+                    stats.max_depth = depth - 1;
+                } else {
+                    stats.max_depth = depth;
+                }
+                return (best_play, sign * best_score);
+            }
+            depth += 1
+        }
+    }
+    
+}
+
+impl<T: BoardState + Send> BasicAi<T> {
+
+    /// Search `state` with [`BasicAi::iddfs`] on this thread, while `self.threads - 1` helper
+    /// threads search the same position ("Lazy SMP"). The threads share the transposition table,
+    /// so the helpers' results speed up the main search. Half of the helpers search one ply ahead
+    /// of the main thread, so that the threads don't all duplicate the same work. Only the main
+    /// thread's result is used; the nodes visited by the helpers are added to `stats`.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn search_with_helpers(
+        &mut self,
+        state: GameState<T>,
+        stats: &mut SearchStats,
+        posn_history: &mut Vec<Position<T>>,
+        time_to_play: Duration,
+        cancel: &CancelToken,
+        on_progress: &mut dyn FnMut(&SearchProgress),
+        info: &mut SearchInfo
+    ) -> (Option<ValidPlay>, i32) {
+        let helper_cancel = CancelToken::new();
+        std::thread::scope(|s| {
+            let helpers: Vec<_> = (1..self.threads).map(|i| {
+                let mut helper = self.clone();
+                helper.depth_offset = (i % 2) as u8;
+                let mut posns = posn_history.clone();
+                let helper_cancel = helper_cancel.clone();
+                s.spawn(move || {
+                    let mut helper_stats = SearchStats::default();
+                    helper.iddfs(
+                        state,
+                        &mut helper_stats,
+                        &mut posns,
+                        time_to_play,
+                        &helper_cancel,
+                        &mut |_| {},
+                        &mut SearchInfo::default()
+                    );
+                    helper_stats.states
+                })
+            }).collect();
+            let result = self.iddfs(
+                state, stats, posn_history, time_to_play, cancel, on_progress, info
+            );
+            helper_cancel.cancel();
+            for h in helpers {
+                stats.states += h.join().expect("Search helper thread panicked");
+            }
+            result
+        })
+    }
+
+    /// Search `game_state` until it runs out of time or `stop` is cancelled, using helper threads
+    /// if configured to. Returns the best play found (if any) and information about the search.
+    fn search(
+        &mut self,
+        game_state: &GameState<T>,
+        posn_history: &[Position<T>],
+        time_to_play: Duration,
+        stop: &CancelToken,
+        on_progress: &mut dyn FnMut(&SearchProgress)
+    ) -> (Option<ValidPlay>, SearchInfo) {
+        let mut stats = SearchStats::default();
+        let mut info = SearchInfo::default();
+        let mut posns = Vec::with_capacity(200);
+        posns.extend_from_slice(posn_history);
+        let start_time = Instant::now();
+        self.tt.new_search();
+        #[cfg(not(target_arch = "wasm32"))]
+        let (best_play, _) = if self.threads > 1 {
+            self.search_with_helpers(
+                *game_state,
+                &mut stats,
+                &mut posns,
+                time_to_play,
+                stop,
+                on_progress,
+                &mut info
+            )
+        } else {
+            self.iddfs(*game_state, &mut stats, &mut posns, time_to_play, stop, on_progress, &mut info)
+        };
+        #[cfg(target_arch = "wasm32")]
+        let (best_play, _) = self.iddfs(
+            *game_state,
+            &mut stats,
+            &mut posns,
+            time_to_play,
+            stop,
+            on_progress,
+            &mut info
+        );
+        info.stats = stats;
+        info.elapsed = start_time.elapsed();
+        (best_play, info)
+    }
+
+    /// Decide which play to make after a search of `game_state` found `best_play`. This is
+    /// usually `best_play`, but may be a random play if [`BasicAi::blunder_chance`] is set.
+    fn choose_play(
+        &self,
+        game_state: &GameState<T>,
+        best_play: Option<ValidPlay>,
+        info: SearchInfo
+    ) -> Result<(ValidPlay, SearchInfo), AiError> {
+        let mut rng = thread_rng();
+        if best_play.is_some() && self.blunder_chance > 0.0 && rng.gen_bool(self.blunder_chance) {
+            let plays = legal_plays(&self.logic, game_state);
+            return Ok((*plays.choose(&mut rng).expect("No legal plays"), info));
+        }
+
+        match best_play {
+            Some(p) => Ok((p, info)),
+            None => Err(NoPlayAvailable)
+        }
+    }
+
+    /// Search `game_state` until `stop` is cancelled (or the search can't usefully go any
+    /// further), then return the play the AI would make, as [`Ai::next_play`] does when it runs
+    /// out of time. This lets the AI think about a position on its opponent's time, when it
+    /// doesn't yet know how long it will be able to think for.
+    pub fn ponder(
+        &mut self,
+        game_state: &GameState<T>,
+        posn_history: &[Position<T>],
+        stop: &CancelToken,
+        on_progress: &mut dyn FnMut(&SearchProgress)
+    ) -> Result<(ValidPlay, SearchInfo), AiError> {
+        let (best_play, info) = self.search(
+            game_state, posn_history, MAX_PONDER_TIME, stop, on_progress
+        );
+        self.choose_play(game_state, best_play, info)
+    }
+
+    /// Search `game_state` for `time_to_play`, without consulting the opening book or making
+    /// deliberate mistakes, and return the best play found (if any) and information about the
+    /// search, whose score and principal variation are wanted when analysing a position.
+    pub fn analyze(
+        &mut self,
+        game_state: &GameState<T>,
+        posn_history: &[Position<T>],
+        time_to_play: Duration,
+        cancel: &CancelToken,
+        on_progress: &mut dyn FnMut(&SearchProgress)
+    ) -> (Option<ValidPlay>, SearchInfo) {
+        self.search(game_state, posn_history, time_to_play, cancel, on_progress)
+    }
+}
+
+impl<T: BoardState + Send> Ai for BasicAi<T> {
+
+    type BoardState = T;
+
+    fn next_play(
+        &mut self,
+        game_state: &GameState<T>,
+        posn_history: &[Position<T>],
+        time_to_play: Duration,
+        cancel: &CancelToken,
+        on_progress: &mut dyn FnMut(&SearchProgress)
+    ) -> Result<(ValidPlay, SearchInfo), AiError> {
+        // An AI that has been made weaker shouldn't play the book's well-chosen plays
+        let weakened = self.eval_noise != 0 || self.blunder_chance > 0.0;
+        if !weakened {
+            if let Some(play) = self.book_play(game_state) {
+                return Ok((play, SearchInfo::default()));
+            }
+        }
+        if let Some((play, info)) = self.tablebase_play(game_state) {
+            return self.choose_play(game_state, Some(play), info);
+        }
+        let (best_play, info) = self.search(
+            game_state, posn_history, time_to_play, cancel, on_progress
+        );
+        if cancel.is_cancelled() {
+            return Err(AiError::Cancelled);
+        }
+        self.choose_play(game_state, best_play, info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        legal_plays, side_sign, BasicAi, CancelToken, EvalParams, SearchInfo, SearchStats, INFINITY
+    };
+    use crate::variants::OOTB_VARIANTS;
+    use hnefatafl::aliases::{MediumBasicBoardState, MediumBasicGame};
+    use hnefatafl::game::state::{GameState, Position};
+    use hnefatafl::play::ValidPlay;
+    use std::time::{Duration, Instant};
+
+    /// Depth to search each position to.
+    const DEPTH: u8 = 3;
+
+    /// Long enough that the searches are only stopped by reaching [`DEPTH`].
+    const TIME_TO_PLAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn new_ai(game: &MediumBasicGame, eval_params: EvalParams) -> BasicAi<MediumBasicBoardState> {
+        let mut ai = BasicAi::with_tt_size(game.logic, 16);
+        ai.eval_params = eval_params;
+        ai.max_depth = Some(DEPTH);
+        // These make the score found depend on the order in which plays are searched
+        ai.null_move_pruning = false;
+        ai.late_move_reductions = false;
+        ai
+    }
+
+    /// Search `state` as the root was searched before principal variation search and aspiration
+    /// windows were used: at each depth up to [`DEPTH`], every play is searched with the full
+    /// window. Returns the score of each play at the final depth, from the perspective of the side
+    /// to play.
+    fn full_window_scores(
+        ai: &mut BasicAi<MediumBasicBoardState>,
+        state: GameState<MediumBasicBoardState>,
+        posn_history: &mut Vec<Position<MediumBasicBoardState>>,
+        stats: &mut SearchStats
+    ) -> Vec<(ValidPlay, i32)> {
+        let cancel = CancelToken::new();
+        let cutoff_time = Instant::now() + TIME_TO_PLAY;
+        let root_hash = ai.zt.hash(state.board, state.side_to_play);
+        ai.age_heuristics();
+        let mut scores = Vec::new();
+        for depth in 1..=DEPTH {
+            scores.clear();
+            for play in legal_plays(&ai.logic, &state) {
+                let child = ai.logic.do_valid_play(play, state, Some(posn_history)).new_state;
+                let child_hash = ai.hash_after(root_hash, play, &state, &child);
+                let score = -ai.negamax(
+                    child, child_hash, depth, 1, -INFINITY, INFINITY, true, posn_history, stats,
+                    &cancel, cutoff_time
+                ).0;
+                scores.push((play, score));
+            }
+        }
+        scores
+    }
+
+    #[test]
+    fn root_search_matches_full_window_search() {
+        for (rules, board, name, eval_params) in OOTB_VARIANTS {
+            let game = MediumBasicGame::new(rules, board).expect("Could not construct game");
+
+            let mut old_stats = SearchStats::default();
+            let scores = full_window_scores(
+                &mut new_ai(&game, eval_params),
+                game.state,
+                &mut game.position_history.clone(),
+                &mut old_stats
+            );
+            let best_score = scores.iter().map(|(_, s)| *s).max().expect("No legal plays");
+
+            let mut new_stats = SearchStats::default();
+            let (play, score) = new_ai(&game, eval_params).iddfs(
+                game.state,
+                &mut new_stats,
+                &mut game.position_history.clone(),
+                TIME_TO_PLAY,
+                &CancelToken::new(),
+                &mut |_| {},
+                &mut SearchInfo::default()
+            );
+            let play = play.expect("No play found");
+
+            // `iddfs` reports scores from the attacker's perspective
+            assert_eq!(side_sign(game.state.side_to_play) * score, best_score, "{name}");
+            // Plays with the best score are equally good, and the searches may break ties between
+            // them differently
+            assert!(
+                scores.contains(&(play, best_score)),
+                "{name}: {play} is not among the best plays found with a full window"
+            );
+            assert!(
+                new_stats.states < old_stats.states,
+                "{name}: {} nodes searched, against {} with a full window",
+                new_stats.states,
+                old_stats.states
+            );
+        }
+    }
+}
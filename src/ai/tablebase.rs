@@ -0,0 +1,685 @@
+//! Endgame tablebases: the result of every position with a few pieces left, with perfect play by
+//! both sides, for a particular set of rules and board size.
+//!
+//! A tablebase covers every position with a king, up to a given number of attacking soldiers and
+//! up to a given number of defending soldiers. Positions are grouped by how many of each they have
+//! (their "material"), and each group is solved by retrograde analysis: starting from the
+//! positions in which the side to play can win immediately, or capture its way into a group that
+//! has already been solved, results are propagated backwards through the plays that lead to them.
+//!
+//! Each position is stored as a single byte, giving its distance to the end of the game in plies
+//! (see [`TbResult`]), at an index computed from the squares its pieces occupy, so no space is
+//! wasted on impossible arrangements. Repetitions are not taken into account, and wins which take
+//! more than 255 plies are recorded as draws.
+
+use crate::ai::{opponent, side_sign, BasicAi, DepthInfo, SearchInfo, SearchStats, WIN_SCORE};
+use hnefatafl::board::state::BoardState;
+use hnefatafl::game::logic::GameLogic;
+use hnefatafl::game::state::GameState;
+use hnefatafl::game::GameOutcome::{Draw, Win};
+use hnefatafl::game::GameStatus::{Ongoing, Over};
+use hnefatafl::pieces::PieceType::Soldier;
+use hnefatafl::pieces::Side::{Attacker, Defender};
+use hnefatafl::pieces::{Piece, Side, KING};
+use hnefatafl::play::ValidPlay;
+use hnefatafl::tiles::Tile;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// Magic bytes at the start of a tablebase file, including a format version.
+const TB_MAGIC: &[u8; 8] = b"TAFLTB\0\x01";
+
+/// Directory in which the app looks for tablebases (see [`tablebase_path`]).
+pub const TABLEBASE_DIR: &str = "tablebases";
+
+/// Maximum number of plays in the principal variation reported for a play from the tablebase.
+const TABLEBASE_PV_LEN: usize = 24;
+
+/// Where the app looks for the tablebase for the variant with the given name.
+pub fn tablebase_path(variant_name: &str) -> PathBuf {
+    Path::new(TABLEBASE_DIR).join(format!("{variant_name}.tb"))
+}
+
+/// The result of a position with perfect play, from the perspective of the side to play, and the
+/// number of plies until the game ends.
+///
+/// The side that wins makes the last play, so wins always take an odd number of plies and losses
+/// an even number. Tablebase files store this number, with 0 for a draw.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TbResult {
+    Win(u8),
+    Loss(u8),
+    Draw,
+}
+
+impl TbResult {
+    fn from_byte(b: u8) -> Self {
+        match b {
+            0 => TbResult::Draw,
+            n if n % 2 == 1 => TbResult::Win(n),
+            n => TbResult::Loss(n)
+        }
+    }
+
+    /// The result for the side whose play led to a position with this result.
+    fn for_previous_mover(self) -> Self {
+        match self {
+            TbResult::Win(n) => n.checked_add(1).map_or(TbResult::Draw, TbResult::Loss),
+            TbResult::Loss(n) => n.checked_add(1).map_or(TbResult::Draw, TbResult::Win),
+            TbResult::Draw => TbResult::Draw
+        }
+    }
+}
+
+/// A set of solved endgames (see the module documentation).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Tablebase {
+    side_len: u8,
+    max_attackers: u8,
+    max_defenders: u8,
+    /// The rules the tablebase was generated for, as JSON.
+    rules: String,
+    /// `binom[n][k]` is the number of ways of choosing `k` of `n` squares.
+    binom: Vec<Vec<usize>>,
+    /// The result of each position, indexed first by material (see [`Tablebase::table_index`])
+    /// and then by [`Tablebase::index`].
+    tables: Vec<Vec<u8>>,
+}
+
+/// The squares occupied by the pieces in `squares` (which must be sorted), renumbered to skip the
+/// squares in `taken` (which must also be sorted).
+fn compress(squares: &[usize], taken: &[usize]) -> Vec<usize> {
+    squares.iter().map(|s| s - taken.iter().filter(|t| *t < s).count()).collect()
+}
+
+/// The inverse of [`compress`].
+fn decompress(squares: &[usize], taken: &[usize]) -> Vec<usize> {
+    squares.iter()
+        .map(|s| taken.iter().fold(*s, |s, t| if *t <= s { s + 1 } else { s }))
+        .collect()
+}
+
+impl Tablebase {
+    fn empty(side_len: u8, max_attackers: u8, max_defenders: u8, rules: String) -> Self {
+        let n_tiles = (side_len as usize).pow(2);
+        let max_k = max_attackers.max(max_defenders) as usize;
+        let mut binom = vec![vec![0usize; max_k + 1]; n_tiles + 1];
+        for n in 0..=n_tiles {
+            binom[n][0] = 1;
+            for k in 1..=max_k.min(n) {
+                binom[n][k] = binom[n - 1][k - 1] + binom[n - 1].get(k).copied().unwrap_or(0);
+            }
+        }
+        let n_tables = (max_attackers as usize + 1) * (max_defenders as usize + 1);
+        Self {
+            side_len,
+            max_attackers,
+            max_defenders,
+            rules,
+            binom,
+            tables: vec![Vec::new(); n_tables]
+        }
+    }
+
+    /// Maximum number of attacking soldiers in the positions covered.
+    pub fn max_attackers(&self) -> u8 {
+        self.max_attackers
+    }
+
+    /// Maximum number of defending soldiers (not counting the king) in the positions covered.
+    pub fn max_defenders(&self) -> u8 {
+        self.max_defenders
+    }
+
+    fn n_tiles(&self) -> usize {
+        (self.side_len as usize).pow(2)
+    }
+
+    /// Index in `tables` of the positions with the given material.
+    fn table_index(&self, attackers: u8, defenders: u8) -> usize {
+        attackers as usize * (self.max_defenders as usize + 1) + defenders as usize
+    }
+
+    /// Number of positions with the given material: every square for the king, every combination
+    /// of the remaining squares for the attackers and then for the defenders, and either side to
+    /// play.
+    fn table_len(&self, attackers: u8, defenders: u8) -> usize {
+        let n = self.n_tiles();
+        n * self.binom[n - 1][attackers as usize]
+            * self.binom[n - 1 - attackers as usize][defenders as usize]
+            * 2
+    }
+
+    /// Number of positions covered by the tablebase.
+    pub fn len(&self) -> usize {
+        self.tables.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn square(&self, tile: Tile) -> usize {
+        tile.row as usize * self.side_len as usize + tile.col as usize
+    }
+
+    /// The number of attacking and defending soldiers on `board`, if it is covered by the
+    /// tablebase.
+    fn material<T: BoardState>(&self, board: &T) -> Option<(u8, u8)> {
+        board.get_king()?;
+        let attackers = board.count_pieces_of_side(Attacker) as usize;
+        let defenders = board.count_pieces_of_side(Defender) as usize - 1;
+        (attackers <= self.max_attackers as usize && defenders <= self.max_defenders as usize)
+            .then_some((attackers as u8, defenders as u8))
+    }
+
+    /// Rank of a combination of squares (which must be sorted) in the combinatorial number system.
+    fn rank(&self, squares: &[usize]) -> usize {
+        squares.iter().enumerate().map(|(i, s)| self.binom[*s][i + 1]).sum()
+    }
+
+    /// Index of `state`, which has the given material, in its table.
+    fn index<T: BoardState>(&self, state: &GameState<T>, attackers: u8, defenders: u8) -> usize {
+        let board = &state.board;
+        let king = self.square(board.get_king().expect("Position should have a king"));
+        let mut att: Vec<usize> = board.occupied_by_side(Attacker)
+            .map(|t| self.square(t))
+            .collect();
+        let mut def: Vec<usize> = board.occupied_by_side(Defender)
+            .map(|t| self.square(t))
+            .filter(|s| *s != king)
+            .collect();
+        att.sort_unstable();
+        def.sort_unstable();
+        let mut taken = att.clone();
+        taken.push(king);
+        taken.sort_unstable();
+
+        let n = self.n_tiles();
+        let att_combos = self.binom[n - 1][attackers as usize];
+        let def_combos = self.binom[n - 1 - attackers as usize][defenders as usize];
+        let att_rank = self.rank(&compress(&att, &[king]));
+        let def_rank = self.rank(&compress(&def, &taken));
+        let side = if state.side_to_play == Attacker { 0 } else { 1 };
+        ((king * att_combos + att_rank) * def_combos + def_rank) * 2 + side
+    }
+
+    /// The result of `state`, if it is covered by the tablebase. The position's history (and so
+    /// any repetition) is not taken into account.
+    pub fn probe<T: BoardState>(&self, state: &GameState<T>) -> Option<TbResult> {
+        let (attackers, defenders) = self.material(&state.board)?;
+        let table = &self.tables[self.table_index(attackers, defenders)];
+        let b = table.get(self.index(state, attackers, defenders))?;
+        Some(TbResult::from_byte(*b))
+    }
+
+    /// Load a tablebase written by [`Tablebase::save`]. Returns `None` if it was generated for
+    /// different rules or a different size of board than `logic`'s.
+    pub fn load<T: BoardState>(path: &Path, logic: &GameLogic<T>) -> io::Result<Option<Self>> {
+        let bad_data = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let data = std::fs::read(path)?;
+        if data.len() < 15 || &data[..8] != TB_MAGIC {
+            return Err(bad_data("Not a tablebase"));
+        }
+        let (side_len, max_attackers, max_defenders) = (data[8], data[9], data[10]);
+        let rules_len = u32::from_le_bytes(data[11..15].try_into().expect("Expected 4 bytes"));
+        let Some(rules) = data.get(15..15 + rules_len as usize) else {
+            return Err(bad_data("Truncated tablebase"));
+        };
+        let rules = String::from_utf8(rules.to_vec()).map_err(|_| bad_data("Bad rules"))?;
+        let expected_rules = serde_json::to_string(&logic.rules).map_err(io::Error::other)?;
+        if side_len != logic.board_geo.side_len || rules != expected_rules {
+            return Ok(None)
+        }
+
+        let mut tb = Self::empty(side_len, max_attackers, max_defenders, rules);
+        let mut rest = &data[15 + rules_len as usize..];
+        for attackers in 0..=max_attackers {
+            for defenders in 0..=max_defenders {
+                let len = tb.table_len(attackers, defenders);
+                if rest.len() < len {
+                    return Err(bad_data("Truncated tablebase"));
+                }
+                let i = tb.table_index(attackers, defenders);
+                tb.tables[i] = rest[..len].to_vec();
+                rest = &rest[len..];
+            }
+        }
+        Ok(Some(tb))
+    }
+}
+
+/// What is known about the plays available in a position while its table is being solved.
+#[derive(Debug, Clone, Copy, Default)]
+struct PlayInfo {
+    /// Number of plays leading to positions in the same table whose result is not yet known.
+    pending: u16,
+    /// Length of the quickest win by a play leaving the table, or 0 if there is none.
+    exit_win: u8,
+    /// Length of the slowest loss by a play leaving the table, or 0 if there is none.
+    exit_loss: u8,
+    /// Whether a play leaving the table leads to a draw.
+    exit_draw: bool,
+}
+
+impl Tablebase {
+    /// Solve every position with a king, up to `max_attackers` attacking soldiers and up to
+    /// `max_defenders` defending soldiers, under the rules and on the board of `logic`, using
+    /// `threads` threads. `on_table` is called with each material as it is solved, along with
+    /// the number of positions solved and how many of those were not draws.
+    ///
+    /// The number of positions grows very quickly with the number of pieces, so this is only
+    /// practical for a few pieces on small boards. Not available on WASM, which can't spawn
+    /// threads.
+    pub fn generate<T: BoardState + Send>(
+        logic: GameLogic<T>,
+        max_attackers: u8,
+        max_defenders: u8,
+        threads: usize,
+        on_table: &mut dyn FnMut(u8, u8, usize, usize)
+    ) -> Self {
+        let rules = serde_json::to_string(&logic.rules).expect("Could not serialise rules");
+        let mut tb = Self::empty(logic.board_geo.side_len, max_attackers, max_defenders, rules);
+        let side_len = tb.side_len as usize;
+        let empty = T::from_fen(&vec![side_len.to_string(); side_len].join("/"))
+            .expect("Could not construct empty board");
+        // Captures only ever reduce the material, so every table a play can lead to has been
+        // solved by the time it is needed.
+        for attackers in 0..=max_attackers {
+            for defenders in 0..=max_defenders {
+                let values = tb.solve(logic, empty, attackers, defenders, threads);
+                let decided = values.iter().filter(|v| **v != 0).count();
+                on_table(attackers, defenders, values.len(), decided);
+                let i = tb.table_index(attackers, defenders);
+                tb.tables[i] = values;
+            }
+        }
+        tb
+    }
+
+    /// Write the tablebase to `path`: a header, giving the board size, the maximum material and
+    /// the rules (as length-prefixed JSON), followed by each table in turn.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        w.write_all(TB_MAGIC)?;
+        w.write_all(&[self.side_len, self.max_attackers, self.max_defenders])?;
+        w.write_all(&(self.rules.len() as u32).to_le_bytes())?;
+        w.write_all(self.rules.as_bytes())?;
+        for table in &self.tables {
+            w.write_all(table)?;
+        }
+        w.flush()
+    }
+
+    fn tile(&self, square: usize) -> Tile {
+        let side_len = self.side_len as usize;
+        Tile::new((square / side_len) as u8, (square % side_len) as u8)
+    }
+
+    /// The inverse of [`Tablebase::rank`], for combinations of `k` squares.
+    fn unrank(&self, mut rank: usize, k: usize) -> Vec<usize> {
+        let mut squares = vec![0; k];
+        let mut c = self.n_tiles();
+        for i in (0..k).rev() {
+            // The largest square below the previous one whose binomial fits in what is left
+            c -= 1;
+            while self.binom[c][i + 1] > rank {
+                c -= 1;
+            }
+            squares[i] = c;
+            rank -= self.binom[c][i + 1];
+        }
+        squares
+    }
+
+    /// The position with the given index in the table for the given material, set up on `empty`.
+    fn state_at<T: BoardState>(
+        &self,
+        empty: T,
+        index: usize,
+        attackers: u8,
+        defenders: u8
+    ) -> GameState<T> {
+        let n = self.n_tiles();
+        let att_combos = self.binom[n - 1][attackers as usize];
+        let def_combos = self.binom[n - 1 - attackers as usize][defenders as usize];
+        let side = if index % 2 == 0 { Attacker } else { Defender };
+        let rest = index / 2;
+        let def_rank = rest % def_combos;
+        let rest = rest / def_combos;
+        let att_rank = rest % att_combos;
+        let king = rest / att_combos;
+
+        let att = decompress(&self.unrank(att_rank, attackers as usize), &[king]);
+        let mut taken = att.clone();
+        taken.push(king);
+        taken.sort_unstable();
+        let def = decompress(&self.unrank(def_rank, defenders as usize), &taken);
+
+        let mut board = empty;
+        board.set_piece(self.tile(king), KING);
+        for s in att {
+            board.set_piece(self.tile(s), Piece::new(Soldier, Attacker));
+        }
+        for s in def {
+            board.set_piece(self.tile(s), Piece::new(Soldier, Defender));
+        }
+        GameState { turn: 0, board, side_to_play: side, plays_since_capture: 0, status: Ongoing }
+    }
+
+    /// The result, for `mover`, of the play by `mover` which led to `child`, if it doesn't depend
+    /// on the table with the given material, which is being solved.
+    fn play_result<T: BoardState>(
+        &self,
+        child: &GameState<T>,
+        mover: Side,
+        attackers: u8,
+        defenders: u8
+    ) -> Option<TbResult> {
+        match child.status {
+            Over(Win(_, winner)) if winner == mover => Some(TbResult::Win(1)),
+            // No standard rules make a side lose by its own play, but if they do, treat it as if
+            // the opponent had won with its next play.
+            Over(Win(..)) => Some(TbResult::Loss(2)),
+            Over(Draw(_)) => Some(TbResult::Draw),
+            Ongoing if self.material(&child.board) == Some((attackers, defenders)) => None,
+            Ongoing => Some(self.probe(child).map_or(TbResult::Draw, TbResult::for_previous_mover))
+        }
+    }
+
+    /// Examine each play available in the position with the given index.
+    fn examine<T: BoardState>(
+        &self,
+        logic: GameLogic<T>,
+        empty: T,
+        index: usize,
+        attackers: u8,
+        defenders: u8
+    ) -> PlayInfo {
+        let state = self.state_at(empty, index, attackers, defenders);
+        let mut info = PlayInfo::default();
+        for t in state.board.occupied_by_side(state.side_to_play) {
+            // Arrangements which can't arise in a game (eg, a soldier on the throne) may have no
+            // valid plays, but nothing can lead to them either
+            let Ok(plays) = logic.iter_plays(t, &state) else {
+                continue
+            };
+            for vp in plays {
+                let child = logic.do_valid_play(vp, state, None).new_state;
+                match self.play_result(&child, state.side_to_play, attackers, defenders) {
+                    None => info.pending += 1,
+                    Some(TbResult::Win(n)) => {
+                        info.exit_win = if info.exit_win == 0 { n } else { info.exit_win.min(n) };
+                    },
+                    Some(TbResult::Loss(n)) => info.exit_loss = info.exit_loss.max(n),
+                    Some(TbResult::Draw) => info.exit_draw = true
+                }
+            }
+        }
+        info
+    }
+
+    /// Indices of the positions in the same table from which a play leads to the position with
+    /// the given index. Found by moving each of the last mover's pieces back along each open line,
+    /// and checking that the play forward again is valid and leads to the same position.
+    fn predecessors<T: BoardState>(
+        &self,
+        logic: GameLogic<T>,
+        empty: T,
+        index: usize,
+        attackers: u8,
+        defenders: u8
+    ) -> Vec<usize> {
+        let state = self.state_at(empty, index, attackers, defenders);
+        let mover = opponent(state.side_to_play);
+        let side_len = self.side_len as i16;
+        let mut preds = Vec::new();
+        for to in state.board.occupied_by_side(mover) {
+            let piece = state.board.get_piece(to).expect("There should be a piece here.");
+            for (d_row, d_col) in [(0, 1), (0, -1), (1, 0), (-1, 0)] {
+                let (mut row, mut col) = (to.row as i16 + d_row, to.col as i16 + d_col);
+                while (0..side_len).contains(&row) && (0..side_len).contains(&col) {
+                    let from = Tile::new(row as u8, col as u8);
+                    if state.board.get_piece(from).is_some() {
+                        break
+                    }
+                    let mut prev = state;
+                    prev.board.clear_tile(to);
+                    prev.board.set_piece(from, piece);
+                    prev.side_to_play = mover;
+                    let play = logic.iter_plays(from, &prev).ok()
+                        .and_then(|mut plays| plays.find(|vp| vp.play.to() == to));
+                    if let Some(vp) = play {
+                        let child = logic.do_valid_play(vp, prev, None).new_state;
+                        if child.status == Ongoing
+                            && self.material(&child.board) == Some((attackers, defenders))
+                            && self.index(&child, attackers, defenders) == index {
+                            preds.push(self.index(&prev, attackers, defenders));
+                        }
+                    }
+                    row += d_row;
+                    col += d_col;
+                }
+            }
+        }
+        preds
+    }
+
+    /// Solve the positions with the given material, all of whose smaller materials must already
+    /// have been solved. Returns the table of results.
+    fn solve<T: BoardState + Send>(
+        &self,
+        logic: GameLogic<T>,
+        empty: T,
+        attackers: u8,
+        defenders: u8,
+        threads: usize
+    ) -> Vec<u8> {
+        let len = self.table_len(attackers, defenders);
+
+        // Examining every play is by far the most expensive part, so do it in parallel
+        let mut info = vec![PlayInfo::default(); len];
+        let chunk_len = len.div_ceil(threads.max(1));
+        std::thread::scope(|s| {
+            for (i, chunk) in info.chunks_mut(chunk_len).enumerate() {
+                s.spawn(move || {
+                    for (j, pi) in chunk.iter_mut().enumerate() {
+                        *pi = self.examine(logic, empty, i * chunk_len + j, attackers, defenders);
+                    }
+                });
+            }
+        });
+
+        // Positions to be decided once each number of plies is reached. A position is decided the
+        // first time it comes up, which is with its quickest win, or (as a position only comes up
+        // as a loss once all its plays are known to lose) its slowest loss.
+        let mut queue: Vec<Vec<usize>> = vec![Vec::new(); u8::MAX as usize + 1];
+        for (i, pi) in info.iter().enumerate() {
+            if pi.exit_win > 0 {
+                queue[pi.exit_win as usize].push(i);
+            } else if pi.pending == 0 && !pi.exit_draw && pi.exit_loss > 0 {
+                queue[pi.exit_loss as usize].push(i);
+            }
+        }
+
+        let mut values = vec![0u8; len];
+        for plies in 1..=u8::MAX {
+            for i in std::mem::take(&mut queue[plies as usize]) {
+                if values[i] != 0 {
+                    continue
+                }
+                values[i] = plies;
+                let Some(next) = plies.checked_add(1) else {
+                    continue
+                };
+                for p in self.predecessors(logic, empty, i, attackers, defenders) {
+                    if values[p] != 0 {
+                        continue
+                    }
+                    if matches!(TbResult::from_byte(plies), TbResult::Loss(_)) {
+                        // Playing into a lost position wins
+                        queue[next as usize].push(p);
+                    } else {
+                        let pi = &mut info[p];
+                        pi.pending -= 1;
+                        if pi.pending == 0 && pi.exit_win == 0 && !pi.exit_draw {
+                            // Every play loses, so hold out for as long as possible
+                            queue[next.max(pi.exit_loss) as usize].push(p);
+                        }
+                    }
+                }
+            }
+        }
+        values
+    }
+}
+
+impl<T: BoardState> BasicAi<T> {
+    /// The score of `state`, found `ply` plies from the root of the search, from the perspective
+    /// of the side to play, if it is covered by [`BasicAi::tablebase`].
+    pub(crate) fn tablebase_score(&self, state: &GameState<T>, ply: u8) -> Option<i32> {
+        let tb = self.tablebase.as_ref()?;
+        Some(match tb.probe(state)? {
+            TbResult::Win(n) => WIN_SCORE - ply.saturating_add(n) as i32,
+            TbResult::Loss(n) => -WIN_SCORE + ply.saturating_add(n) as i32,
+            TbResult::Draw => 0
+        })
+    }
+
+    /// The best play in `state` according to [`BasicAi::tablebase`], and its score from the
+    /// perspective of the side to play, if every play leads to a position it covers.
+    fn tablebase_best(&self, state: &GameState<T>) -> Option<(ValidPlay, i32)> {
+        let side = state.side_to_play;
+        let mut best: Option<(ValidPlay, i32)> = None;
+        for t in state.board.occupied_by_side(side) {
+            for vp in self.logic.iter_plays(t, state).ok()? {
+                let child = self.logic.do_valid_play(vp, *state, None).new_state;
+                let score = if child.status == Ongoing {
+                    -self.tablebase_score(&child, 1)?
+                } else {
+                    side_sign(side) * self.eval_state(&child, 1)
+                };
+                if best.is_none_or(|(_, s)| score > s) {
+                    best = Some((vp, score));
+                }
+            }
+        }
+        best
+    }
+
+    /// If `state` is covered by [`BasicAi::tablebase`], the best play and information about it in
+    /// the same form as a search would give, so that a forced win is reported in the same way.
+    pub(crate) fn tablebase_play(&self, state: &GameState<T>) -> Option<(ValidPlay, SearchInfo)> {
+        let start_time = Instant::now();
+        self.tablebase.as_ref()?.probe(state)?;
+        let (play, score) = self.tablebase_best(state)?;
+        let mut pv = vec![play];
+        let mut next = self.logic.do_valid_play(play, *state, None).new_state;
+        while next.status == Ongoing && pv.len() < TABLEBASE_PV_LEN {
+            let Some((p, _)) = self.tablebase_best(&next) else {
+                break
+            };
+            pv.push(p);
+            next = self.logic.do_valid_play(p, next, None).new_state;
+        }
+        let depth = pv.len() as u8;
+        let elapsed = start_time.elapsed();
+        Some((play, SearchInfo {
+            depths: vec![DepthInfo {
+                depth,
+                score: side_sign(state.side_to_play) * score,
+                pv,
+                nodes: 0,
+                elapsed
+            }],
+            stats: SearchStats { tb_hits: 1, max_depth: depth, ..SearchStats::default() },
+            elapsed
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Tablebase, TbResult};
+    use crate::variants::OOTB_VARIANTS;
+    use hnefatafl::aliases::{MediumBasicBoardState, MediumBasicGame};
+    use hnefatafl::board::state::BoardState;
+    use hnefatafl::game::state::GameState;
+    use hnefatafl::game::GameStatus::Ongoing;
+    use hnefatafl::pieces::Side;
+    use hnefatafl::pieces::Side::{Attacker, Defender};
+
+    fn game(name: &str) -> MediumBasicGame {
+        let (rules, board, _, _) = OOTB_VARIANTS.into_iter()
+            .find(|(_, _, n, _)| *n == name)
+            .expect("Unknown variant");
+        MediumBasicGame::new(rules, board).expect("Could not construct game")
+    }
+
+    /// A tablebase of Brandubh positions with a king and at most one attacker.
+    fn brandubh_tablebase() -> Tablebase {
+        Tablebase::generate(game("Brandubh").logic, 1, 0, 2, &mut |_, _, _, _| {})
+    }
+
+    fn state(fen: &str, side_to_play: Side) -> GameState<MediumBasicBoardState> {
+        let board = MediumBasicBoardState::from_fen(fen).expect("Bad board");
+        GameState { turn: 0, board, side_to_play, plays_since_capture: 0, status: Ongoing }
+    }
+
+    #[test]
+    fn unrank_inverts_rank() {
+        let tb = Tablebase::empty(3, 3, 3, String::new());
+        for mask in 0u32..(1 << 9) {
+            let squares: Vec<usize> = (0..9).filter(|s| mask & (1 << s) != 0).collect();
+            if squares.len() > 3 {
+                continue
+            }
+            let rank = tb.rank(&squares);
+            assert!(rank < tb.binom[9][squares.len()], "{squares:?} ranked {rank}");
+            assert_eq!(tb.unrank(rank, squares.len()), squares);
+        }
+    }
+
+    #[test]
+    fn state_at_inverts_index() {
+        let logic = game("Brandubh").logic;
+        let tb = Tablebase::empty(logic.board_geo.side_len, 1, 1, String::new());
+        let empty = MediumBasicBoardState::from_fen("7/7/7/7/7/7/7").expect("Bad board");
+        for (attackers, defenders) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            for index in 0..tb.table_len(attackers, defenders) {
+                let state = tb.state_at(empty, index, attackers, defenders);
+                assert_eq!(tb.material(&state.board), Some((attackers, defenders)));
+                assert_eq!(tb.index(&state, attackers, defenders), index);
+            }
+        }
+    }
+
+    #[test]
+    fn probe_known_results() {
+        let tb = brandubh_tablebase();
+        // The king can reach a corner at once
+        assert_eq!(tb.probe(&state("3K3/7/7/7/7/7/7", Defender)), Some(TbResult::Win(1)));
+        // The attacker can only block one of the king's two routes to a corner
+        assert_eq!(tb.probe(&state("3K3/7/7/7/7/5t1/7", Attacker)), Some(TbResult::Loss(2)));
+        // Too much material to be covered
+        assert_eq!(tb.probe(&state("3K3/7/7/7/7/3tt2/7", Attacker)), None);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let tb = brandubh_tablebase();
+        let path = std::env::temp_dir().join(format!("taflr-tb-test-{}", std::process::id()));
+        tb.save(&path).expect("Could not save tablebase");
+        let loaded = Tablebase::load(&path, &game("Brandubh").logic);
+        let other_rules = Tablebase::load(&path, &game("Copenhagen").logic);
+        std::fs::remove_file(&path).expect("Could not remove tablebase");
+        assert_eq!(loaded.expect("Could not load tablebase"), Some(tb));
+        assert_eq!(other_rules.expect("Could not load tablebase"), None);
+    }
+}
@@ -0,0 +1,501 @@
+use crate::ai::{BasicAi, SearchStats};
+use hnefatafl::board::state::BoardState;
+use hnefatafl::play::ValidPlay;
+use hnefatafl::tiles::Tile;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+
+/// Number of entries in each bucket of the table.
+const BUCKET_SIZE: usize = 4;
+
+/// Number of slots in each bucket (starting from the first) that are reserved for the most
+/// valuable entries. The remaining slot is always replaced.
+const DEPTH_PREFERRED_SLOTS: usize = BUCKET_SIZE - 1;
+
+/// Value of [`TTEntry::play`] when no best play is stored.
+const NO_PLAY: u32 = u32::MAX;
+
+/// Mask for the node type in [`TTEntry::flags`]. The remaining bits store the age.
+const NODE_TYPE_MASK: u8 = 0b11;
+
+/// Ages are stored in 6 bits, so wrap at 64.
+const AGE_MASK: u8 = 0b11_1111;
+
+/// Identifies a transposition table snapshot file, and the version of its format.
+const SNAPSHOT_MAGIC: &[u8; 8] = b"TAFLTT\0\x01";
+
+/// Length of a snapshot's header: the magic bytes, the number of buckets and the current age.
+const SNAPSHOT_HEADER_LEN: usize = 8 + 8 + 1;
+
+/// Length of each entry in a snapshot: the bucket index, the slot index and the entry's two words.
+const SNAPSHOT_ENTRY_LEN: usize = 4 + 1 + 8 + 8;
+
+/// Entries shallower than this aren't worth saving in snapshots, as they are cheap to recompute.
+const SNAPSHOT_MIN_DEPTH: u8 = 2;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum NodeType {
+    LowerBound,
+    UpperBound,
+    Exact
+}
+
+impl NodeType {
+    /// Encode the node type in the lower bits of [`TTEntry::flags`]. Never 0, which denotes an
+    /// empty slot.
+    fn to_bits(self) -> u8 {
+        match self {
+            NodeType::LowerBound => 1,
+            NodeType::UpperBound => 2,
+            NodeType::Exact => 3
+        }
+    }
+
+    fn from_bits(bits: u8) -> Option<Self> {
+        match bits & NODE_TYPE_MASK {
+            1 => Some(NodeType::LowerBound),
+            2 => Some(NodeType::UpperBound),
+            3 => Some(NodeType::Exact),
+            _ => None
+        }
+    }
+}
+
+/// Pack the origin and destination of a play into a `u32`. The play can be recovered (given the
+/// position) with [`BasicAi::resolve_play`](crate::ai::BasicAi).
+fn pack_play(play: ValidPlay) -> u32 {
+    let from = play.play.from;
+    let to = play.play.to();
+    u32::from_be_bytes([from.row, from.col, to.row, to.col])
+}
+
+fn unpack_play(packed: u32) -> Option<(Tile, Tile)> {
+    if packed == NO_PLAY {
+        return None
+    }
+    let [from_row, from_col, to_row, to_col] = packed.to_be_bytes();
+    Some((Tile::new(from_row, from_col), Tile::new(to_row, to_col)))
+}
+
+/// An entry in the transposition table. Stored packed into two `u64`s (see [`Slot`]), so that a
+/// whole bucket fits in a typical cache line.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct TTEntry {
+    /// The upper 32 bits of the position's hash. The lower bits are implied by the bucket the
+    /// entry is in.
+    key: u32,
+    /// The best play found in the position, packed by [`pack_play`].
+    play: u32,
+    pub(crate) score: i32,
+    pub(crate) depth: u8,
+    /// The node type in the lower 2 bits (0 if the slot is empty) and the age of the search that
+    /// stored the entry in the upper 6 bits.
+    flags: u8
+}
+
+impl TTEntry {
+    fn new(
+        key: u32,
+        depth: u8,
+        score: i32,
+        node_type: NodeType,
+        best_play: Option<ValidPlay>,
+        age: u8
+    ) -> Self {
+        Self {
+            key,
+            play: best_play.map_or(NO_PLAY, pack_play),
+            score,
+            depth,
+            flags: (age << 2) | node_type.to_bits()
+        }
+    }
+
+    /// Pack the entry into two words: the play and score, and the key, depth and flags.
+    fn to_words(self) -> (u64, u64) {
+        let data = ((self.play as u64) << 32) | (self.score as u32 as u64);
+        let meta = ((self.key as u64) << 32) | ((self.depth as u64) << 8) | (self.flags as u64);
+        (data, meta)
+    }
+
+    fn from_words(data: u64, meta: u64) -> Self {
+        Self {
+            key: (meta >> 32) as u32,
+            play: (data >> 32) as u32,
+            score: data as u32 as i32,
+            depth: (meta >> 8) as u8,
+            flags: meta as u8
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.flags & NODE_TYPE_MASK == 0
+    }
+
+    fn age(&self) -> u8 {
+        self.flags >> 2
+    }
+
+    pub(crate) fn node_type(&self) -> NodeType {
+        NodeType::from_bits(self.flags).expect("Empty entry has no node type.")
+    }
+
+    /// The origin and destination tiles of the best play found in the position, if any.
+    pub(crate) fn best_play_tiles(&self) -> Option<(Tile, Tile)> {
+        unpack_play(self.play)
+    }
+}
+
+/// A slot holding a single entry, which can be read and written by several threads at once
+/// without locking.
+///
+/// The entry is stored as two words, the second XORed with the first. If a thread reads the slot
+/// while another is writing it, it may see one word from the old entry and one from the new; the
+/// key recovered from the mismatched words will then (almost certainly) not match the position
+/// being probed, so the torn entry is ignored. Any best play retrieved is checked for legality
+/// before use anyway.
+#[derive(Debug, Default)]
+struct Slot {
+    data: AtomicU64,
+    check: AtomicU64
+}
+
+impl Slot {
+    fn load(&self) -> TTEntry {
+        let data = self.data.load(Ordering::Relaxed);
+        let check = self.check.load(Ordering::Relaxed);
+        TTEntry::from_words(data, check ^ data)
+    }
+
+    fn store(&self, entry: TTEntry) {
+        let (data, meta) = entry.to_words();
+        self.data.store(data, Ordering::Relaxed);
+        self.check.store(meta ^ data, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Default)]
+#[repr(C, align(64))]
+struct Bucket([Slot; BUCKET_SIZE]);
+
+/// A transposition table, storing the results of previous searches keyed by Zobrist hash.
+///
+/// Each hash maps to a bucket of several entries. Most slots in a bucket prefer to keep entries
+/// that are deep or from the current search, as these save the most work; the last slot is always
+/// replaced, so that recent results are available even when the rest of the bucket is full of
+/// valuable entries.
+///
+/// Cloning the table produces a handle to the same underlying table, which can be shared between
+/// threads searching in parallel.
+#[derive(Debug, Clone)]
+pub(crate) struct TranspositionTable {
+    buckets: Arc<[Bucket]>,
+    current_age: Arc<AtomicU8>
+}
+
+impl PartialEq for TranspositionTable {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.buckets, &other.buckets)
+    }
+}
+
+impl Eq for TranspositionTable {}
+
+impl TranspositionTable {
+    pub(crate) fn new(size_mb: usize) -> Self {
+        let n_buckets = ((size_mb * 1024 * 1024) / std::mem::size_of::<Bucket>()).max(1);
+        Self {
+            buckets: (0..n_buckets).map(|_| Bucket::default()).collect(),
+            current_age: Arc::new(AtomicU8::new(0))
+        }
+    }
+
+    /// Mark the start of a new search, so that entries from previous searches are replaced in
+    /// preference to those from this one. Should be called once per search, not by each thread.
+    pub(crate) fn new_search(&self) {
+        let age = self.current_age.load(Ordering::Relaxed);
+        self.current_age.store((age + 1) & AGE_MASK, Ordering::Relaxed);
+    }
+
+    fn current_age(&self) -> u8 {
+        self.current_age.load(Ordering::Relaxed)
+    }
+
+    fn bucket_index(&self, hash: u64) -> usize {
+        (hash as usize) % self.buckets.len()
+    }
+
+    fn key(hash: u64) -> u32 {
+        (hash >> 32) as u32
+    }
+
+    /// How valuable an entry is to keep. Empty slots are worthless, and entries from the current
+    /// search are worth more than any from previous searches, and then deeper entries are worth
+    /// more.
+    fn value(&self, entry: &TTEntry, current_age: u8) -> u16 {
+        if entry.is_empty() {
+            0
+        } else if entry.age() == current_age {
+            256 + entry.depth as u16
+        } else {
+            1 + entry.depth as u16
+        }
+    }
+
+    pub(crate) fn insert(
+        &self,
+        hash: u64,
+        depth: u8,
+        score: i32,
+        node_type: NodeType,
+        best_play: Option<ValidPlay>,
+        stats: &mut SearchStats
+    ) {
+        let key = Self::key(hash);
+        let age = self.current_age();
+        let entry = TTEntry::new(key, depth, score, node_type, best_play, age);
+        let bucket = &self.buckets[self.bucket_index(hash)];
+        let existing: [TTEntry; BUCKET_SIZE] = std::array::from_fn(|i| bucket.0[i].load());
+
+        // If the position is already stored, update it, unless the existing entry is from a
+        // deeper search in this generation.
+        if let Some(slot) = existing.iter().position(|e| !e.is_empty() && e.key == key) {
+            if depth >= existing[slot].depth || existing[slot].age() != age {
+                bucket.0[slot].store(entry);
+                stats.tt_replacements += 1;
+            }
+            return
+        }
+
+        // Otherwise, replace the least valuable of the depth-preferred slots if the new entry is
+        // at least as valuable, and failing that, the always-replace slot.
+        let slot = (0..DEPTH_PREFERRED_SLOTS)
+            .min_by_key(|i| self.value(&existing[*i], age))
+            .filter(|i| self.value(&entry, age) >= self.value(&existing[*i], age))
+            .unwrap_or(BUCKET_SIZE - 1);
+        if existing[slot].is_empty() {
+            stats.tt_inserts += 1;
+        } else {
+            stats.tt_replacements += 1;
+        }
+        bucket.0[slot].store(entry);
+    }
+
+    pub(crate) fn probe(&self, hash: u64) -> Option<TTEntry> {
+        let key = Self::key(hash);
+        self.buckets[self.bucket_index(hash)].0.iter()
+            .map(Slot::load)
+            .find(|e| !e.is_empty() && e.key == key)
+    }
+
+    /// Write the entries with at least the given depth to `w`.
+    ///
+    /// The snapshot consists of a header (see [`SNAPSHOT_HEADER_LEN`]) followed by each entry's
+    /// position in the table and its two words, all little-endian. Entries only store part of
+    /// their position's hash, so they can only be restored to a table of the same size.
+    fn write_snapshot(&self, w: &mut impl Write, min_depth: u8) -> io::Result<()> {
+        w.write_all(SNAPSHOT_MAGIC)?;
+        w.write_all(&(self.buckets.len() as u64).to_le_bytes())?;
+        w.write_all(&[self.current_age()])?;
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            for (j, slot) in bucket.0.iter().enumerate() {
+                let entry = slot.load();
+                if entry.is_empty() || entry.depth < min_depth {
+                    continue
+                }
+                let (data, meta) = entry.to_words();
+                w.write_all(&(i as u32).to_le_bytes())?;
+                w.write_all(&[j as u8])?;
+                w.write_all(&data.to_le_bytes())?;
+                w.write_all(&meta.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Restore the entries in a snapshot written by [`TranspositionTable::write_snapshot`],
+    /// overwriting whatever is in their slots. Returns `false`, without restoring anything, if the
+    /// snapshot was taken from a table of a different size. A truncated snapshot is restored as
+    /// far as it goes.
+    fn read_snapshot(&self, r: &mut impl Read) -> io::Result<bool> {
+        let mut header = [0u8; SNAPSHOT_HEADER_LEN];
+        r.read_exact(&mut header)?;
+        if &header[..8] != SNAPSHOT_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not a transposition table snapshot"
+            ));
+        }
+        if le_u64(&header[8..16]) != self.buckets.len() as u64 {
+            return Ok(false)
+        }
+        self.current_age.store(header[16] & AGE_MASK, Ordering::Relaxed);
+        let mut record = [0u8; SNAPSHOT_ENTRY_LEN];
+        loop {
+            match r.read_exact(&mut record) {
+                Ok(()) => {},
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e)
+            }
+            let bucket = u32::from_le_bytes([record[0], record[1], record[2], record[3]]) as usize;
+            let slot = record[4] as usize;
+            let (Some(bucket), true) = (self.buckets.get(bucket), slot < BUCKET_SIZE) else {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "Bad snapshot entry"));
+            };
+            bucket.0[slot].store(TTEntry::from_words(le_u64(&record[5..13]), le_u64(&record[13..])));
+        }
+        Ok(true)
+    }
+}
+
+/// Read a little-endian `u64` from exactly 8 bytes.
+fn le_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes.try_into().expect("Expected 8 bytes"))
+}
+
+impl<T: BoardState> BasicAi<T> {
+    /// Save the more valuable entries in the transposition table to `path`, so that a later
+    /// session can pick up where this one left off (see [`BasicAi::load_tt_snapshot`]).
+    pub fn save_tt_snapshot(&self, path: &Path) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.tt.write_snapshot(&mut w, SNAPSHOT_MIN_DEPTH)?;
+        w.flush()
+    }
+
+    /// Restore transposition table entries saved by [`BasicAi::save_tt_snapshot`]. Returns
+    /// `false`, without restoring anything, if the snapshot was saved by an AI whose table was a
+    /// different size.
+    ///
+    /// The snapshot should have been saved by an AI playing the same variant.
+    pub fn load_tt_snapshot(&self, path: &Path) -> io::Result<bool> {
+        self.tt.read_snapshot(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NodeType, Slot, TTEntry, TranspositionTable, SNAPSHOT_MAGIC};
+    use crate::ai::{legal_plays, BasicAi, SearchStats};
+    use crate::variants::OOTB_VARIANTS;
+    use hnefatafl::aliases::MediumBasicGame;
+    use hnefatafl::play::ValidPlay;
+    use std::io;
+
+    const HASH: u64 = 0x0123_4567_89ab_cdef;
+
+    fn brandubh() -> MediumBasicGame {
+        let (rules, board, _, _) = OOTB_VARIANTS.into_iter()
+            .find(|(_, _, n, _)| *n == "Brandubh")
+            .expect("No Brandubh variant");
+        MediumBasicGame::new(rules, board).expect("Could not construct game")
+    }
+
+    fn some_play() -> ValidPlay {
+        let game = brandubh();
+        legal_plays(&game.logic, &game.state)[0]
+    }
+
+    /// A hash which maps to the same bucket as `hash` in `tt`, but with a different key.
+    fn colliding_hash(tt: &TranspositionTable, hash: u64) -> u64 {
+        hash.wrapping_add((tt.buckets.len() as u64) << 32)
+    }
+
+    #[test]
+    fn insert_and_probe() {
+        let tt = TranspositionTable::new(1);
+        let play = some_play();
+        tt.insert(HASH, 5, -1234, NodeType::UpperBound, Some(play), &mut SearchStats::default());
+        let entry = tt.probe(HASH).expect("Entry not found");
+        assert_eq!(entry.depth, 5);
+        assert_eq!(entry.score, -1234);
+        assert_eq!(entry.node_type(), NodeType::UpperBound);
+        assert_eq!(entry.best_play_tiles(), Some((play.play.from, play.play.to())));
+
+        let other = colliding_hash(&tt, HASH);
+        assert_eq!(tt.bucket_index(other), tt.bucket_index(HASH));
+        assert_eq!(tt.probe(other), None);
+    }
+
+    #[test]
+    fn torn_slot_is_rejected() {
+        let old = TTEntry::new(1, 3, 10, NodeType::Exact, None, 0);
+        let new = TTEntry::new(2, 4, 20, NodeType::LowerBound, Some(some_play()), 0);
+        let slot = Slot::default();
+        slot.store(old);
+        assert_eq!(slot.load(), old);
+        // Another thread has written the first word of a new entry, but not yet the second
+        slot.data.store(new.to_words().0, std::sync::atomic::Ordering::Relaxed);
+        let torn = slot.load();
+        assert_ne!(torn.key, old.key);
+        assert_ne!(torn.key, new.key);
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let tt = TranspositionTable::new(1);
+        let mut stats = SearchStats::default();
+        tt.new_search();
+        tt.insert(HASH, 3, 50, NodeType::Exact, Some(some_play()), &mut stats);
+        let shallow_hash = colliding_hash(&tt, HASH);
+        tt.insert(shallow_hash, 1, 60, NodeType::Exact, None, &mut stats);
+
+        let mut snapshot = Vec::new();
+        tt.write_snapshot(&mut snapshot, 2).expect("Could not write snapshot");
+        let restored = TranspositionTable::new(1);
+        assert!(restored.read_snapshot(&mut snapshot.as_slice()).expect("Could not read snapshot"));
+        assert_eq!(restored.current_age(), tt.current_age());
+        assert_eq!(restored.probe(HASH), tt.probe(HASH));
+        assert_eq!(restored.probe(shallow_hash), None);
+    }
+
+    #[test]
+    fn snapshot_file_round_trip() {
+        let game = brandubh();
+        let ai = BasicAi::with_tt_size(game.logic, 1);
+        ai.tt.insert(HASH, 4, 70, NodeType::LowerBound, None, &mut SearchStats::default());
+        let path = std::env::temp_dir().join(format!("taflr-tt-test-{}", std::process::id()));
+        ai.save_tt_snapshot(&path).expect("Could not save snapshot");
+        let loaded = BasicAi::with_tt_size(game.logic, 1);
+        let result = loaded.load_tt_snapshot(&path);
+        std::fs::remove_file(&path).expect("Could not remove snapshot");
+        assert!(result.expect("Could not load snapshot"));
+        assert_eq!(loaded.tt.probe(HASH), ai.tt.probe(HASH));
+    }
+
+    #[test]
+    fn snapshot_of_different_size_is_not_restored() {
+        let tt = TranspositionTable::new(1);
+        tt.insert(HASH, 3, 50, NodeType::Exact, None, &mut SearchStats::default());
+        let mut snapshot = Vec::new();
+        tt.write_snapshot(&mut snapshot, 0).expect("Could not write snapshot");
+        let other = TranspositionTable::new(2);
+        assert!(!other.read_snapshot(&mut snapshot.as_slice()).expect("Could not read snapshot"));
+        assert_eq!(other.probe(HASH), None);
+    }
+
+    #[test]
+    fn bad_snapshot_is_rejected() {
+        let tt = TranspositionTable::new(1);
+        let mut snapshot = Vec::new();
+        tt.write_snapshot(&mut snapshot, 0).expect("Could not write snapshot");
+
+        let mut bad_magic = snapshot.clone();
+        bad_magic[0] ^= 0xff;
+        let err = tt.read_snapshot(&mut bad_magic.as_slice()).expect_err("Bad magic accepted");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let err = tt.read_snapshot(&mut &SNAPSHOT_MAGIC[..]).expect_err("Short header accepted");
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        // An entry in a bucket beyond the end of the table
+        let mut bad_entry = snapshot;
+        bad_entry.extend_from_slice(&u32::MAX.to_le_bytes());
+        bad_entry.extend_from_slice(&[0; 17]);
+        let err = tt.read_snapshot(&mut bad_entry.as_slice()).expect_err("Bad entry accepted");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
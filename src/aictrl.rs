@@ -1,48 +1,470 @@
-use crate::ai::{Ai, BasicAi};
-use dioxus::prelude::{ReadableExt, Signal};
+use crate::ai::{Difficulty, SearchInfo, SearchProgress};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ai::{
+    load_opening_books, tablebase_path, Ai, AiError, BasicAi, CancelToken, MctsAi, Tablebase,
+    OPENING_BOOK_FILE
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::app_settings::APP_SETTINGS;
+use crate::app_settings::AppSettings;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::external;
+use crate::gamectrl::{AiEngine, ExternalEngine};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ponder::{stop_pondering, take_ponder_hit};
+use crate::variants::Variant;
+#[cfg(not(target_arch = "wasm32"))]
+use dioxus::prelude::ReadableExt;
+use dioxus::prelude::{spawn, Signal, WritableExt};
 use dioxus::signals::GlobalSignal;
 use hnefatafl::aliases::MediumBasicBoardState;
 use hnefatafl::board::state::BoardState;
+use hnefatafl::game::logic::GameLogic;
 use hnefatafl::game::state::{GameState, Position};
-use hnefatafl::play::ValidPlay;
+use hnefatafl::play::{Play, ValidPlay};
 use std::time::Duration;
 
+#[cfg(not(target_arch = "wasm32"))]
 pub static AI: GlobalSignal<Option<BasicAi<MediumBasicBoardState>>> = Signal::global(|| None);
 
+/// The Monte Carlo AI, used by players whose engine is [`AiEngine::Mcts`].
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) static MCTS_AI: GlobalSignal<Option<MctsAi<MediumBasicBoardState>>> =
+    Signal::global(|| None);
+
+/// Token for cancelling the AI search currently in progress, if any.
+#[cfg(not(target_arch = "wasm32"))]
+static SEARCH_CANCEL: GlobalSignal<Option<CancelToken>> = Signal::global(|| None);
+
+/// Progress of the AI search currently in progress, if any.
+pub(crate) static AI_PROGRESS: GlobalSignal<Option<SearchProgress>> = Signal::global(|| None);
+
 pub(crate) struct AiRequest<B: BoardState> {
     pub(crate) game_state: GameState<B>,
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
     pub(crate) posn_history: Vec<Position<B>>,
+    /// Every play made so far in the game, from which the web worker or an external engine
+    /// reconstructs the game.
+    pub(crate) plays: Vec<Play>,
     pub(crate) time_to_play: Duration,
+    /// The AI implementation to use.
+    pub(crate) engine: AiEngine,
+    /// The level at which the AI should play.
+    pub(crate) difficulty: Difficulty,
+    /// The program to ask for a play, if the engine is [`AiEngine::External`].
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub(crate) external_engine: ExternalEngine,
 }
 
 #[derive(Debug)]
 pub(crate) struct AiResponse<B: BoardState> {
     pub(crate) game_state: GameState<B>,
-    pub(crate) play: ValidPlay
+    pub(crate) play: ValidPlay,
+    /// Information about the search that produced the play.
+    pub(crate) info: SearchInfo
+}
+
+/// The result of an AI search: `Ok(None)` if it was cancelled.
+pub(crate) type AiResult = Result<Option<AiResponse<MediumBasicBoardState>>, String>;
+
+/// The game (by database id) and transposition table size that [`AI`] was set up for.
+#[cfg(not(target_arch = "wasm32"))]
+static AI_GAME: GlobalSignal<Option<(i64, usize)>> = Signal::global(|| None);
+
+/// Directory in which transposition table snapshots are saved.
+#[cfg(not(target_arch = "wasm32"))]
+const TT_SNAPSHOT_DIR: &str = "tt_snapshots";
+
+/// Path of the transposition table snapshot for the game with the given database id.
+#[cfg(not(target_arch = "wasm32"))]
+fn tt_snapshot_path(db_id: i64) -> std::path::PathBuf {
+    std::path::Path::new(TT_SNAPSHOT_DIR).join(format!("{db_id}.tt"))
+}
+
+/// Prepare the AIs to play the game with database id `db_id`, a game of `variant` using the given
+/// game logic. If the AIs are already set up for that game, they are kept, so that the
+/// transposition table built up during the game isn't lost. Otherwise, if enabled, the table is
+/// restored from the snapshot saved when the game was last left (see [`save_tt_snapshot`]).
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn setup_ai(
+    db_id: i64,
+    logic: GameLogic<MediumBasicBoardState>,
+    variant: &Variant,
+    app_settings: AppSettings
+) {
+    external::setup(logic, variant);
+    if *AI_GAME.read() == Some((db_id, app_settings.tt_size_mb)) {
+        if let Some(ai) = AI.write().as_mut() {
+            ai.threads = app_settings.search_threads;
+            return
+        }
+    }
+    let mut ai = BasicAi::with_tt_size(logic, app_settings.tt_size_mb);
+    ai.eval_params = variant.eval_params;
+    ai.threads = app_settings.search_threads;
+    if app_settings.save_tt {
+        let path = tt_snapshot_path(db_id);
+        match ai.load_tt_snapshot(&path) {
+            Ok(true) => {},
+            // The AI memory setting has changed since the snapshot was saved
+            Ok(false) => eprintln!("Ignoring snapshot {} of a different size", path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+            Err(e) => eprintln!("Could not load snapshot {}: {e}", path.display())
+        }
+    }
+    match load_opening_books(std::path::Path::new(OPENING_BOOK_FILE)) {
+        Ok(mut books) => ai.opening_book = books.remove(&variant.name).map(std::sync::Arc::new),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+        Err(e) => eprintln!("Could not load opening books from {OPENING_BOOK_FILE}: {e}")
+    }
+    let tb_path = tablebase_path(&variant.name);
+    match Tablebase::load(&tb_path, &logic) {
+        Ok(Some(tb)) => ai.tablebase = Some(std::sync::Arc::new(tb)),
+        // The variant's rules have changed since the tablebase was generated
+        Ok(None) => eprintln!("Ignoring tablebase {} for different rules", tb_path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+        Err(e) => eprintln!("Could not load tablebase {}: {e}", tb_path.display())
+    }
+    *AI.write() = Some(ai);
+    *MCTS_AI.write() = Some(MctsAi::new(logic));
+    *AI_GAME.write() = Some((db_id, app_settings.tt_size_mb));
+}
+
+/// Prepare the AIs to play the game with database id `db_id`, a game of `variant` using the given
+/// game logic. On the web, the AIs live in a web worker, which is sent the variant to set them up.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn setup_ai(
+    db_id: i64,
+    logic: GameLogic<MediumBasicBoardState>,
+    variant: &Variant,
+    app_settings: AppSettings
+) {
+    web_worker::setup(db_id, logic, variant, app_settings.tt_size_mb);
+}
+
+/// If enabled, save a snapshot of the AI's transposition table for the game with database id
+/// `db_id`, in the background, so that it can be restored when the game is next loaded.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn save_tt_snapshot(db_id: i64) {
+    if !APP_SETTINGS.read().save_tt || AI_GAME.read().map(|(id, _)| id) != Some(db_id) {
+        return
+    }
+    // The clone shares the original's table
+    let Some(ai) = AI.read().clone() else {
+        return
+    };
+    std::thread::spawn(move || {
+        let path = tt_snapshot_path(db_id);
+        if let Err(e) = std::fs::create_dir_all(TT_SNAPSHOT_DIR)
+            .and_then(|_| ai.save_tt_snapshot(&path)) {
+            eprintln!("Could not save snapshot {}: {e}", path.display());
+        }
+    });
+}
+
+/// Snapshots can't be saved on the web.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn save_tt_snapshot(_db_id: i64) {}
+
+/// Delete the transposition table snapshot for the game with database id `db_id`, if any.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn delete_tt_snapshot(db_id: i64) {
+    match std::fs::remove_file(tt_snapshot_path(db_id)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            eprintln!("Could not delete snapshot for game {db_id}: {e}");
+        },
+        _ => {}
+    }
+}
+
+/// Snapshots can't be saved on the web.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn delete_tt_snapshot(_db_id: i64) {}
+
+/// Cancel the AI search currently in progress (including any pondering), if any. The search
+/// thread will stop at the next node it visits. On the web, the worker can't be interrupted, so it
+/// is replaced by a new one instead.
+pub(crate) fn cancel_ai_play() {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        if let Some(cancel) = SEARCH_CANCEL.write().take() {
+            cancel.cancel();
+        }
+        stop_pondering();
+    }
+    #[cfg(target_arch = "wasm32")]
+    web_worker::abandon_search();
+}
+
+/// Compute the AI's play in a separate thread (or, on the web, in a web worker). If the AI has
+/// been pondering the requested position, its search is continued instead. Returns `Ok(None)` if
+/// the search was cancelled (see [`cancel_ai_play`]) before it completed.
+pub(crate) async fn compute_ai_play(request: AiRequest<MediumBasicBoardState>) -> AiResult {
+    #[cfg(not(target_arch = "wasm32"))]
+    let ponder = take_ponder_hit(&request);
+    // Only one search should be running at a time
+    cancel_ai_play();
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(ponder) = ponder {
+        let cancel = CancelToken::new();
+        *SEARCH_CANCEL.write() = Some(cancel.clone());
+        let (rx, progress_rx) = ponder.finish(request.time_to_play, cancel);
+        forward_progress(progress_rx);
+        return rx.await.map_err(|_| "AI error".to_string())?
+    }
+
+    let (progress_tx, progress_rx) = tokio::sync::watch::channel(None);
+    forward_progress(progress_rx);
+    #[cfg(not(target_arch = "wasm32"))]
+    let rx = search_in_thread(request, progress_tx);
+    #[cfg(target_arch = "wasm32")]
+    let rx = web_worker::search(request, progress_tx);
+    rx.await.map_err(|_| "AI error".to_string())?
+}
+
+/// Forward progress updates from the AI thread to `AI_PROGRESS`. The loop ends when the AI
+/// thread finishes and drops the sender.
+fn forward_progress(mut progress_rx: tokio::sync::watch::Receiver<Option<SearchProgress>>) {
+    spawn(async move {
+        while progress_rx.changed().await.is_ok() {
+            let progress = *progress_rx.borrow_and_update();
+            *AI_PROGRESS.write() = progress;
+        }
+        *AI_PROGRESS.write() = None;
+    });
 }
 
-pub(crate) async fn compute_ai_play(request: AiRequest<MediumBasicBoardState>) -> Result<AiResponse<MediumBasicBoardState>, String> {
+/// Start a search in a new thread (or, for an external engine, in another process), returning a
+/// receiver for its result.
+#[cfg(not(target_arch = "wasm32"))]
+fn search_in_thread(
+    request: AiRequest<MediumBasicBoardState>,
+    progress_tx: tokio::sync::watch::Sender<Option<SearchProgress>>
+) -> tokio::sync::oneshot::Receiver<AiResult> {
+    let cancel = CancelToken::new();
+    *SEARCH_CANCEL.write() = Some(cancel.clone());
+
     let (tx, rx) = tokio::sync::oneshot::channel();
-    let ai_clone = AI.read().clone();
+    let ai_clone: Option<Box<dyn Ai<BoardState = MediumBasicBoardState> + Send>> =
+        match request.engine {
+            AiEngine::AlphaBeta => AI.read().clone().map(|mut ai| {
+                ai.set_difficulty(request.difficulty);
+                Box::new(ai) as _
+            }),
+            AiEngine::Mcts => MCTS_AI.read().clone().map(|ai| Box::new(ai) as _),
+            AiEngine::External => return external::search_in_thread(request, cancel),
+        };
     std::thread::spawn(move || {
+        let mut on_progress = |p: &SearchProgress| {
+            // Nobody may be listening any more, which is fine
+            let _ = progress_tx.send(Some(*p));
+        };
         match ai_clone {
             Some(mut ai) => {
-                match ai.next_play(&request.game_state, &request.posn_history, request.time_to_play) {
-                    Ok((ai_play, _)) => tx.send(Ok(AiResponse {
+                let result = match ai.next_play(
+                    &request.game_state,
+                    &request.posn_history,
+                    request.time_to_play,
+                    &cancel,
+                    &mut on_progress
+                ) {
+                    Ok((ai_play, info)) => Ok(Some(AiResponse {
                         game_state: request.game_state,
-                        play: ai_play
-                    }))
-                        .expect("Could not send AI play from AI thread"),
-                    Err(e) => tx.send(Err(format!("Bad AI play: {:?}", e)))
-                        .expect("Could not send error from AI thread")
-
-                }
+                        play: ai_play,
+                        info
+                    })),
+                    Err(AiError::Cancelled) => Ok(None),
+                    Err(e) => Err(format!("Bad AI play: {:?}", e))
+                };
+                // The receiver may already have been dropped if the game view was closed during
+                // the search, so ignore any error sending.
+                let _ = tx.send(result);
             },
             None => {
-                tx.send(Err("No AI set".to_string()))
-                    .expect("AI was not set, and that error could not be sent from AI thread");
+                let _ = tx.send(Err("No AI set".to_string()));
             }
         }
     });
-    rx.await.map_err(|_| "AI error".to_string())?
-}
\ No newline at end of file
+    rx
+}
+
+/// The web build's connection to the web worker in which the AI runs (see [`taflr::worker`]).
+#[cfg(target_arch = "wasm32")]
+mod web_worker {
+    use crate::ai::SearchProgress;
+    use crate::aictrl::{AiRequest, AiResponse, AiResult};
+    use crate::gamectrl::AiEngine;
+    use crate::variants::Variant;
+    use gloo_worker::{Spawnable, WorkerBridge};
+    use hnefatafl::aliases::MediumBasicBoardState;
+    use hnefatafl::game::logic::GameLogic;
+    use hnefatafl::game::state::GameState;
+    use std::cell::RefCell;
+    use taflr::worker::{AiWorker, WorkerEngine, WorkerInput, WorkerOutput};
+    use tokio::sync::{oneshot, watch};
+
+    /// URL of the script that loads the worker.
+    const WORKER_URL: &str = "/ai_worker.js";
+
+    /// A search that the worker has been asked to perform, whose result is still wanted.
+    struct PendingSearch {
+        id: u64,
+        game_state: GameState<MediumBasicBoardState>,
+        progress_tx: watch::Sender<Option<SearchProgress>>,
+        tx: oneshot::Sender<AiResult>,
+    }
+
+    struct Connection {
+        bridge: WorkerBridge<AiWorker>,
+        /// Logic of the game the worker was last set up for, used to turn the plays it sends back
+        /// into [`hnefatafl::play::ValidPlay`]s.
+        logic: Option<GameLogic<MediumBasicBoardState>>,
+        /// The message with which the worker was last set up, to set up its replacement if it
+        /// is replaced (see [`abandon`]).
+        setup: Option<WorkerInput>,
+        /// Id of the last search requested.
+        last_id: u64,
+        pending: Option<PendingSearch>,
+    }
+
+    thread_local! {
+        /// The connection to the worker, which is started when it is first needed and then kept
+        /// for the rest of the session.
+        static CONNECTION: RefCell<Option<Connection>> = const { RefCell::new(None) };
+    }
+
+    fn with_connection<R>(f: impl FnOnce(&mut Connection) -> R) -> R {
+        CONNECTION.with_borrow_mut(|conn| {
+            let conn = conn.get_or_insert_with(|| Connection {
+                bridge: spawn_worker(),
+                logic: None,
+                setup: None,
+                last_id: 0,
+                pending: None,
+            });
+            f(conn)
+        })
+    }
+
+    fn spawn_worker() -> WorkerBridge<AiWorker> {
+        AiWorker::spawner().callback(handle_output).spawn(WORKER_URL)
+    }
+
+    pub(super) fn setup(
+        db_id: i64,
+        logic: GameLogic<MediumBasicBoardState>,
+        variant: &Variant,
+        tt_size_mb: usize
+    ) {
+        let pending = with_connection(|conn| {
+            let setup = WorkerInput::Setup {
+                game_id: db_id,
+                rules: serde_json::to_string(&variant.rules).expect("Could not serialise rules"),
+                starting_board: variant.starting_board.clone(),
+                eval_params: variant.eval_params,
+                tt_size_mb,
+            };
+            conn.logic = Some(logic);
+            conn.setup = Some(setup.clone());
+            let pending = abandon(conn);
+            // If a search was abandoned, the replacement worker has already been set up
+            if pending.is_none() {
+                conn.bridge.send(setup);
+            }
+            pending
+        });
+        if let Some(pending) = pending {
+            // Nobody may be waiting for the result any more, which is fine
+            let _ = pending.tx.send(Ok(None));
+        }
+    }
+
+    /// Ask the worker to search, returning a receiver for the result.
+    pub(super) fn search(
+        request: AiRequest<MediumBasicBoardState>,
+        progress_tx: watch::Sender<Option<SearchProgress>>
+    ) -> oneshot::Receiver<AiResult> {
+        let (tx, rx) = oneshot::channel();
+        let engine = match request.engine {
+            AiEngine::AlphaBeta => WorkerEngine::AlphaBeta(request.difficulty),
+            AiEngine::Mcts => WorkerEngine::Mcts,
+            AiEngine::External => {
+                // Not offered on the web, but the game may have been set up elsewhere
+                let _ = tx.send(Err("External engines are not available on the web".to_string()));
+                return rx
+            }
+        };
+        with_connection(|conn| {
+            conn.last_id += 1;
+            let id = conn.last_id;
+            conn.bridge.send(WorkerInput::Search {
+                id,
+                plays: request.plays.iter().map(|p| p.to_string()).collect(),
+                time_to_play: request.time_to_play,
+                engine,
+            });
+            conn.pending = Some(PendingSearch {
+                id,
+                game_state: request.game_state,
+                progress_tx,
+                tx,
+            });
+        });
+        rx
+    }
+
+    /// Stop the pending search, if any, and report it as cancelled.
+    pub(super) fn abandon_search() {
+        let pending = CONNECTION.with_borrow_mut(|conn| abandon(conn.as_mut()?));
+        if let Some(pending) = pending {
+            // Nobody may be waiting for the result any more, which is fine
+            let _ = pending.tx.send(Ok(None));
+        }
+    }
+
+    /// Stop the pending search, if any, returning it. A worker handles one message at a time, so
+    /// it can't be told to stop searching. Instead, it is replaced by a new worker, set up for the
+    /// same game, so that the next search doesn't have to wait for the abandoned one. The old
+    /// worker exits when its search ends, as its bridge has been dropped.
+    fn abandon(conn: &mut Connection) -> Option<PendingSearch> {
+        let pending = conn.pending.take()?;
+        conn.bridge = spawn_worker();
+        if let Some(setup) = &conn.setup {
+            conn.bridge.send(setup.clone());
+        }
+        Some(pending)
+    }
+
+    /// Handle a message from the worker. Messages about searches other than the pending one are
+    /// ignored.
+    fn handle_output(output: WorkerOutput) {
+        CONNECTION.with_borrow_mut(|conn| {
+            let Some(conn) = conn.as_mut() else {
+                return
+            };
+            let Some(logic) = conn.logic else {
+                return
+            };
+            match output {
+                WorkerOutput::Progress { id, progress } => {
+                    if let Some(pending) = conn.pending.as_ref().filter(|p| p.id == id) {
+                        let progress = progress.to_progress(&logic, &pending.game_state);
+                        let _ = pending.progress_tx.send(Some(progress));
+                    }
+                },
+                WorkerOutput::Done { id, result } => {
+                    let Some(pending) = conn.pending.take_if(|p| p.id == id) else {
+                        return
+                    };
+                    let game_state = pending.game_state;
+                    let response = result.and_then(|r| r.to_result(&logic, &game_state)
+                        .map(|(play, info)| Some(AiResponse { game_state, play, info }))
+                        .ok_or_else(|| "AI worker sent an invalid play".to_string())
+                    );
+                    let _ = pending.tx.send(response);
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,38 @@
+use dioxus::prelude::*;
+use crate::ai::{default_search_threads, DEFAULT_TT_SIZE_MB};
+
+/// Smallest size, in megabytes, the user may choose for the AI's transposition table.
+pub(crate) const MIN_TT_SIZE_MB: usize = 16;
+
+/// Largest size, in megabytes, the user may choose for the AI's transposition table. A table much
+/// larger than this may not be allocatable, which would abort the app.
+pub(crate) const MAX_TT_SIZE_MB: usize = 4096;
+
+/// Application-wide settings, which persist between sessions.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub(crate) struct AppSettings {
+    /// Size of the AI's transposition table, in megabytes (between [`MIN_TT_SIZE_MB`] and
+    /// [`MAX_TT_SIZE_MB`]).
+    pub(crate) tt_size_mb: usize,
+    /// Number of threads the AI searches with. Ignored on WASM.
+    pub(crate) search_threads: usize,
+    /// Whether the AI keeps thinking during a human opponent's turn. Ignored on WASM.
+    pub(crate) ponder: bool,
+    /// Whether to save the AI's transposition table when leaving a game, and restore it when the
+    /// game is next loaded. Ignored on WASM.
+    pub(crate) save_tt: bool,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            tt_size_mb: DEFAULT_TT_SIZE_MB,
+            search_threads: default_search_threads(),
+            ponder: false,
+            save_tt: false,
+        }
+    }
+}
+
+/// The current application settings, loaded from the database at startup.
+pub(crate) static APP_SETTINGS: GlobalSignal<AppSettings> = Signal::global(AppSettings::default);
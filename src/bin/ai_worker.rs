@@ -0,0 +1,13 @@
+//! The web worker in which the web build of the app runs the AI (see `taflr::worker`). It is built
+//! separately from the app; see the README for how.
+
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    use gloo_worker::Registrable;
+    taflr::worker::AiWorker::registrar().register();
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    eprintln!("taflr-ai-worker only runs in a browser, as a web worker for the web build of taflr.");
+}
@@ -0,0 +1,200 @@
+//! Build opening books for `BasicAi`, from the games saved in the app's database and from games
+//! played between two `BasicAi`s. Each play made in the first few plies of a game is added to
+//! the book for the game's variant, with plays by the eventual winner counting double.
+//!
+//! Runs headless, and writes the books to a JSON file which the app loads when a game starts.
+//! Books already in the file are added to, not replaced.
+//!
+//! Usage: `taflr-book [--db <file>] [--self-play <games>] [--ms <per play>] [--plies <n>]
+//! [--min-weight <n>] [--variant <name>] [--out <file>]`
+//!
+//! `--self-play` is the number of games to play per out-of-the-box variant (default 0).
+
+use hnefatafl::aliases::{MediumBasicBoardState, MediumBasicGame};
+use hnefatafl::game::GameOutcome::Win;
+use hnefatafl::game::GameStatus::Over;
+use hnefatafl::pieces::Side;
+use hnefatafl::play::Play;
+use hnefatafl::rules::Ruleset;
+use sqlx::{Row, SqlitePool};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use taflr::ai::{load_opening_books, save_opening_books, BasicAi, OpeningBook, OPENING_BOOK_FILE};
+use taflr::selfplay::play_game;
+use taflr::variants::OOTB_VARIANTS;
+
+/// Size of each self-playing AI's transposition table.
+const TT_SIZE_MB: usize = 64;
+
+/// Number of random plays at the start of each self-play game, so that games differ from each
+/// other. These are not added to the book.
+const RANDOM_PLIES: usize = 2;
+
+/// Self-play games that haven't ended after this many plays are treated as draws.
+const MAX_PLAYS: usize = 300;
+
+struct Args {
+    db: PathBuf,
+    self_play: usize,
+    time_to_play: Duration,
+    plies: usize,
+    min_weight: u32,
+    variant: Option<String>,
+    out: PathBuf,
+}
+
+fn parse_args() -> Args {
+    let mut args = Args {
+        db: PathBuf::from("taflr.sqlite"),
+        self_play: 0,
+        time_to_play: Duration::from_millis(500),
+        plies: 12,
+        min_weight: 2,
+        variant: None,
+        out: PathBuf::from(OPENING_BOOK_FILE),
+    };
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().unwrap_or_else(|| panic!("Missing value for {arg}"));
+        match arg.as_str() {
+            "--db" => args.db = PathBuf::from(value()),
+            "--self-play" => args.self_play = value().parse()
+                .expect("Number of games should be a number"),
+            "--ms" => args.time_to_play = Duration::from_millis(
+                value().parse().expect("Time per play should be a whole number of milliseconds")
+            ),
+            "--plies" => args.plies = value().parse().expect("Plies should be a number"),
+            "--min-weight" => args.min_weight = value().parse()
+                .expect("Minimum weight should be a number"),
+            "--variant" => args.variant = Some(value()),
+            "--out" => args.out = PathBuf::from(value()),
+            other => panic!("Unrecognised argument: {other}")
+        }
+    }
+    args
+}
+
+/// A finished game to learn from.
+struct Record {
+    variant: String,
+    rules: Ruleset,
+    starting_board: String,
+    plays: Vec<Play>,
+}
+
+/// Load every game saved in the database at `path`, with its variant.
+async fn load_saved_games(path: &PathBuf) -> Result<Vec<Record>, sqlx::Error> {
+    let pool = SqlitePool::connect(&format!("sqlite://{}", path.display())).await?;
+    let games = sqlx::query(
+        r"SELECT games.id, variants.name, variants.rules, variants.starting_board
+          FROM games JOIN variants ON games.variant_name = variants.name"
+    ).fetch_all(&pool).await?;
+    let mut records = Vec::with_capacity(games.len());
+    for g in games {
+        let id: i64 = g.try_get("id")?;
+        let plays = sqlx::query(r"SELECT play FROM play_records WHERE game_id = ? ORDER BY turn")
+            .bind(id)
+            .fetch_all(&pool)
+            .await?
+            .iter()
+            .map(|r| Ok(Play::from_str(r.try_get("play")?).expect("Bad play in database")))
+            .collect::<Result<_, sqlx::Error>>()?;
+        records.push(Record {
+            variant: g.try_get("name")?,
+            rules: serde_json::from_str(g.try_get("rules")?).expect("Bad rules in database"),
+            starting_board: g.try_get("starting_board")?,
+            plays,
+        });
+    }
+    Ok(records)
+}
+
+/// Add the first `plies` plays of `record`, except for the first `skip`, to `book`. Games whose
+/// plays can't be replayed (eg, because the variant's rules have changed since) are only used as
+/// far as they go.
+fn add_to_book(book: &mut OpeningBook, record: &Record, skip: usize, plies: usize) {
+    let Ok(mut game) = MediumBasicGame::new(record.rules, &record.starting_board) else {
+        eprintln!("Could not construct game of {}", record.variant);
+        return
+    };
+    let hasher: BasicAi<MediumBasicBoardState> = BasicAi::with_tt_size(game.logic, 1);
+    let mut entries: Vec<(u64, Side, Play)> = Vec::new();
+    for (i, play) in record.plays.iter().enumerate() {
+        let hash = hasher.book_hash(&game.state);
+        let side = game.state.side_to_play;
+        if game.do_play(*play).is_err() {
+            break
+        }
+        if (skip..plies).contains(&i) {
+            entries.push((hash, side, *play));
+        }
+    }
+    let winner = match game.state.status {
+        Over(Win(_, side)) => Some(side),
+        _ => None
+    };
+    for (hash, side, play) in entries {
+        book.add(hash, play, if winner == Some(side) { 2 } else { 1 });
+    }
+}
+
+fn main() {
+    let args = parse_args();
+    let mut books: HashMap<String, OpeningBook> = match load_opening_books(&args.out) {
+        Ok(books) => books,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+        Err(e) => panic!("Could not load existing books from {}: {e}", args.out.display())
+    };
+    let wanted = |name: &str| args.variant.as_ref().is_none_or(|v| v.as_str() == name);
+
+    if args.db.exists() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Could not start async runtime");
+        let records = runtime.block_on(load_saved_games(&args.db))
+            .expect("Could not load saved games");
+        println!("Adding {} saved games from {}...", records.len(), args.db.display());
+        for record in records.iter().filter(|r| wanted(&r.variant)) {
+            add_to_book(books.entry(record.variant.clone()).or_default(), record, 0, args.plies);
+        }
+    } else {
+        println!("No database at {}; skipping saved games.", args.db.display());
+    }
+
+    let mut rng = rand::thread_rng();
+    for (rules, board, name, eval_params) in OOTB_VARIANTS {
+        if args.self_play == 0 || !wanted(name) {
+            continue
+        }
+        println!("{name}: playing {} games...", args.self_play);
+        let logic = MediumBasicGame::new(rules, board).expect("Could not construct game").logic;
+        let mut attacker = BasicAi::with_tt_size(logic, TT_SIZE_MB);
+        let mut defender = BasicAi::with_tt_size(logic, TT_SIZE_MB);
+        attacker.eval_params = eval_params;
+        defender.eval_params = eval_params;
+        for _ in 0..args.self_play {
+            let game = MediumBasicGame::new(rules, board).expect("Could not construct game");
+            let result = play_game(game, &mut attacker, &mut defender, args.time_to_play,
+                                   MAX_PLAYS, RANDOM_PLIES, &mut rng);
+            let record = Record {
+                variant: name.to_string(),
+                rules,
+                starting_board: board.to_string(),
+                plays: result.plays,
+            };
+            // The random plays aren't worth recommending, but the plays after them are
+            add_to_book(books.entry(name.to_string()).or_default(), &record, RANDOM_PLIES,
+                        args.plies);
+        }
+    }
+
+    for (name, book) in books.iter_mut() {
+        book.prune(args.min_weight);
+        println!("{name}: {} positions", book.len());
+    }
+    save_opening_books(&args.out, &books).expect("Could not write opening books");
+    println!("Wrote opening books to {}", args.out.display());
+}
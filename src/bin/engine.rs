@@ -0,0 +1,263 @@
+//! Run `BasicAi` as an engine speaking the OpenTafl Engine Protocol (see
+//! `taflr::engine_protocol`) over stdin and stdout, so that it can be used by other tafl
+//! front-ends and tournament managers, and played against other engines.
+//!
+//! Only the out-of-the-box variants are supported. The variant is recognised by the name given in
+//! the `rules` message or, failing that, by its starting position. The opening book and tablebase
+//! for the variant are used if they are in the working directory, as in the app.
+//!
+//! Usage: `taflr-engine [--tt <megabytes>] [--threads <n>] [--ms <per play>]`
+//!
+//! `--ms` is the time to spend on each play if the front-end doesn't send the state of the clock.
+
+use hnefatafl::aliases::{MediumBasicBoardState, MediumBasicGame};
+use hnefatafl::pieces::Side;
+use hnefatafl::play::Play;
+use hnefatafl::rules::Ruleset;
+use std::io::BufRead;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use taflr::ai::{
+    default_search_threads, load_opening_books, tablebase_path, Ai, BasicAi, CancelToken,
+    EvalParams, Tablebase, DEFAULT_TT_SIZE_MB, OPENING_BOOK_FILE
+};
+use taflr::engine_protocol::{position_fen, rules_field, side_name, Command, Reply};
+use taflr::variants::OOTB_VARIANTS;
+
+/// When playing against the clock, the engine plans to spend its remaining time (not counting
+/// overtime) over this many more plays.
+const PLAYS_TO_GO: u64 = 30;
+
+/// The least time the engine spends on a play.
+const MIN_TIME_TO_PLAY: Duration = Duration::from_millis(50);
+
+struct Args {
+    tt_size_mb: usize,
+    threads: usize,
+    time_to_play: Duration,
+}
+
+fn parse_args() -> Args {
+    let mut args = Args {
+        tt_size_mb: DEFAULT_TT_SIZE_MB,
+        threads: default_search_threads(),
+        time_to_play: Duration::from_secs(5),
+    };
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().unwrap_or_else(|| panic!("Missing value for {arg}"));
+        match arg.as_str() {
+            "--tt" => args.tt_size_mb = value().parse().expect("Table size should be a number"),
+            "--threads" => args.threads = value().parse()
+                .expect("Number of threads should be a number"),
+            "--ms" => args.time_to_play = Duration::from_millis(
+                value().parse().expect("Time per play should be a whole number of milliseconds")
+            ),
+            other => panic!("Unrecognised argument: {other}")
+        }
+    }
+    args
+}
+
+/// The state of the clock, as last reported by the front-end.
+struct Clock {
+    attacker_ms: u64,
+    defender_ms: u64,
+    overtime_ms: u64,
+    attacker_overtimes: u32,
+    defender_overtimes: u32,
+}
+
+impl Clock {
+    /// How long `side` should spend on its next play.
+    fn time_to_play(&self, side: Side) -> Duration {
+        let (remaining_ms, overtimes) = if side == Side::Attacker {
+            (self.attacker_ms, self.attacker_overtimes)
+        } else {
+            (self.defender_ms, self.defender_overtimes)
+        };
+        let mut ms = remaining_ms / PLAYS_TO_GO;
+        if overtimes > 0 {
+            // Leave a margin so as not to use up an overtime period by accident
+            ms = ms.max(self.overtime_ms * 9 / 10);
+        }
+        Duration::from_millis(ms).max(MIN_TIME_TO_PLAY)
+    }
+}
+
+/// The game being played, and the AI set up to play it.
+struct EngineGame {
+    rules: Ruleset,
+    game: MediumBasicGame,
+    ai: BasicAi<MediumBasicBoardState>,
+    /// Whether the side to play was given by a `side` message. Otherwise, until a play is made,
+    /// the side to play is the variant's default, and may be changed by a `play` message.
+    side_given: bool,
+}
+
+/// Make `side` the side to play in the current position of `game`.
+fn set_side_to_play(game: &mut MediumBasicGame, side: Side) {
+    game.state.side_to_play = side;
+    // The history ends with the current position, which has changed
+    game.position_history.pop();
+    game.position_history.push((&game.state).into());
+}
+
+struct Engine {
+    args: Args,
+    game: Option<EngineGame>,
+    clock: Option<Clock>,
+}
+
+/// The out-of-the-box variant described by `rules`, in OpenTafl's rules notation.
+fn find_variant(rules: &str) -> Option<(Ruleset, &'static str, &'static str, EvalParams)> {
+    let name = rules_field(rules, "name");
+    let start = rules_field(rules, "start").map(position_fen);
+    OOTB_VARIANTS.into_iter()
+        .find(|(_, _, n, _)| name.is_some_and(|name| name.eq_ignore_ascii_case(n)))
+        .or_else(|| OOTB_VARIANTS.into_iter().find(|(_, board, _, _)| start == Some(*board)))
+}
+
+impl Engine {
+    fn game_mut(&mut self) -> Result<&mut EngineGame, String> {
+        self.game.as_mut().ok_or_else(|| "No rules have been given".to_string())
+    }
+
+    fn set_rules(&mut self, rules: &str) -> Result<(), String> {
+        let (rules, board, name, eval_params) = find_variant(rules)
+            .ok_or_else(|| format!("Unsupported rules: {rules}"))?;
+        let game = MediumBasicGame::new(rules, board)
+            .map_err(|e| format!("Could not construct game: {e:?}"))?;
+        let mut ai = BasicAi::with_tt_size(game.logic, self.args.tt_size_mb);
+        ai.eval_params = eval_params;
+        ai.threads = self.args.threads;
+        if let Ok(mut books) = load_opening_books(Path::new(OPENING_BOOK_FILE)) {
+            ai.opening_book = books.remove(name).map(Arc::new);
+        }
+        if let Ok(Some(tb)) = Tablebase::load(&tablebase_path(name), &game.logic) {
+            ai.tablebase = Some(Arc::new(tb));
+        }
+        self.game = Some(EngineGame { rules, game, ai, side_given: false });
+        Ok(())
+    }
+
+    fn time_to_play(&self, side: Side) -> Duration {
+        self.clock.as_ref().map_or(self.args.time_to_play, |c| c.time_to_play(side))
+    }
+
+    /// Handle a message from the front-end, returning the reply to send, if any.
+    fn handle(&mut self, command: Command) -> Result<Option<Reply>, String> {
+        match command {
+            Command::Hello => return Ok(Some(Reply::Hello)),
+            Command::Rules(rules) => self.set_rules(&rules)?,
+            Command::Position(position) => {
+                let eg = self.game_mut()?;
+                eg.game = MediumBasicGame::new(eg.rules, position_fen(&position))
+                    .map_err(|e| format!("Bad position {position}: {e:?}"))?;
+                eg.side_given = false;
+            },
+            Command::Side(side) => {
+                let eg = self.game_mut()?;
+                if !eg.game.play_history.is_empty() {
+                    return Err("The side to play can't be changed once plays have been made"
+                        .to_string())
+                }
+                set_side_to_play(&mut eg.game, side);
+                eg.side_given = true;
+            },
+            Command::Clock {
+                attacker_ms,
+                defender_ms,
+                overtime_ms,
+                attacker_overtimes,
+                defender_overtimes
+            } => {
+                self.clock = Some(Clock {
+                    attacker_ms,
+                    defender_ms,
+                    overtime_ms,
+                    attacker_overtimes,
+                    defender_overtimes
+                });
+            },
+            Command::Move(play) => {
+                let play = Play::from_str(&play).map_err(|e| format!("Bad play {play}: {e:?}"))?;
+                self.game_mut()?.game.do_play(play)
+                    .map_err(|e| format!("Invalid play {play}: {e:?}"))?;
+            },
+            Command::Play(side) => {
+                let time_to_play = self.time_to_play(side);
+                let eg = self.game_mut()?;
+                if side != eg.game.state.side_to_play {
+                    if eg.side_given || !eg.game.play_history.is_empty() {
+                        return Err(format!("It is not the {}' turn", side_name(side)))
+                    }
+                    set_side_to_play(&mut eg.game, side);
+                }
+                let (play, _) = eg.ai.next_play(
+                    &eg.game.state,
+                    &eg.game.position_history,
+                    time_to_play,
+                    &CancelToken::new(),
+                    &mut |_| {}
+                ).map_err(|e| format!("Could not find a play: {e:?}"))?;
+                eg.game.do_play(play.play).map_err(|e| format!("Invalid AI play: {e:?}"))?;
+                return Ok(Some(Reply::Move(play.play.to_string())))
+            },
+            Command::Analyze { seconds, .. } => {
+                // Only the principal variation is reported, however many variations are asked
+                // for. The book isn't consulted, as its plays come with no score or variation.
+                let eg = self.game_mut()?;
+                let (play, info) = eg.ai.analyze(
+                    &eg.game.state,
+                    &eg.game.position_history,
+                    Duration::from_secs(seconds),
+                    &CancelToken::new(),
+                    &mut |_| {}
+                );
+                let play = play.ok_or("Could not analyse position: no play available")?;
+                // Search scores are from the attacker's perspective, but the protocol's are from
+                // the perspective of the side to play
+                let score = info.depths.last().map_or(0, |d| d.score);
+                let score = if eg.game.state.side_to_play == Side::Attacker {
+                    score
+                } else {
+                    -score
+                };
+                let pv = match info.principal_variation() {
+                    [] => vec![play.play.to_string()],
+                    pv => pv.iter().map(|p| p.play.to_string()).collect()
+                };
+                return Ok(Some(Reply::Analysis { score, pv }))
+            },
+            Command::Finish | Command::Goodbye => {}
+        }
+        Ok(None)
+    }
+}
+
+fn main() {
+    let mut engine = Engine { args: parse_args(), game: None, clock: None };
+    for line in std::io::stdin().lock().lines() {
+        let line = line.expect("Could not read from stdin");
+        if line.trim().is_empty() {
+            continue
+        }
+        let command = match Command::from_str(&line) {
+            Ok(Command::Goodbye) => break,
+            Ok(command) => command,
+            Err(e) => {
+                // Unrecognised messages are ignored, as the protocol requires
+                eprintln!("{e}");
+                continue
+            }
+        };
+        match engine.handle(command) {
+            Ok(Some(reply)) => println!("{reply}"),
+            Ok(None) => {},
+            Err(e) => println!("{}", Reply::Error(e))
+        }
+    }
+}
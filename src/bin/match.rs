@@ -0,0 +1,259 @@
+//! Play a match between two configurations of `BasicAi`, over a number of games of each
+//! out-of-the-box variant, and report the results. Each pair of games starts from the same
+//! (randomised) opening, with the engines swapping sides for the second game.
+//!
+//! Usage: `taflr-match [--a <config>] [--b <config>] [--games <n>] [--ms <per play>]
+//! [--depth <n>] [--variant <name>]`
+//!
+//! A config is a comma-separated list of `key=value` settings, eg, `nmp=off,qdepth=2`:
+//!
+//! - `nmp`: null-move pruning (`on` or `off`).
+//! - `lmr`: late move reductions (`on` or `off`).
+//! - `qdepth`: maximum quiescence search depth.
+//! - `depth`: maximum search depth (overrides `--depth`).
+//! - `tt`: transposition table size in megabytes.
+//! - `params`: file of evaluation parameters, as written by `taflr-tune`.
+//!
+//! `--games` is the number of games per variant, and is rounded up to an even number. With
+//! `--depth`, each search stops at that depth rather than after a fixed time.
+
+use hnefatafl::aliases::{MediumBasicBoardState, MediumBasicGame};
+use hnefatafl::game::logic::GameLogic;
+use hnefatafl::pieces::Side;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+use taflr::ai::{load_tuned_params, BasicAi, EvalParams};
+use taflr::selfplay::play_game;
+use taflr::variants::OOTB_VARIANTS;
+
+/// Transposition table size used unless a config says otherwise.
+const DEFAULT_TT_SIZE_MB: usize = 64;
+
+/// Number of random plays at the start of each pair of games.
+const RANDOM_PLIES: usize = 2;
+
+/// Games that haven't ended after this many plays are scored as draws.
+const MAX_PLAYS: usize = 300;
+
+/// Time allowed per play when searching to a fixed depth, which is effectively unlimited.
+const FIXED_DEPTH_TIME: Duration = Duration::from_secs(3600);
+
+/// Settings for one of the engines in the match.
+#[derive(Default)]
+struct EngineConfig {
+    null_move_pruning: Option<bool>,
+    late_move_reductions: Option<bool>,
+    quiescence_depth: Option<u8>,
+    max_depth: Option<u8>,
+    tt_size_mb: Option<usize>,
+    params: Option<HashMap<String, EvalParams>>,
+}
+
+impl EngineConfig {
+    fn parse(s: &str) -> Self {
+        let mut config = Self::default();
+        for setting in s.split(',').filter(|s| !s.is_empty()) {
+            let (key, value) = setting.split_once('=')
+                .unwrap_or_else(|| panic!("Bad setting (expected key=value): {setting}"));
+            let on_off = || match value {
+                "on" => true,
+                "off" => false,
+                other => panic!("Bad value for {key} (expected on or off): {other}")
+            };
+            match key {
+                "nmp" => config.null_move_pruning = Some(on_off()),
+                "lmr" => config.late_move_reductions = Some(on_off()),
+                "qdepth" => config.quiescence_depth = Some(value.parse().expect("Bad qdepth")),
+                "depth" => config.max_depth = Some(value.parse().expect("Bad depth")),
+                "tt" => config.tt_size_mb = Some(value.parse().expect("Bad tt")),
+                "params" => config.params = Some(
+                    load_tuned_params(Path::new(value)).expect("Could not load parameters")
+                ),
+                other => panic!("Unrecognised setting: {other}")
+            }
+        }
+        config
+    }
+
+    /// Create an AI for the named variant with these settings.
+    fn build(
+        &self,
+        logic: GameLogic<MediumBasicBoardState>,
+        variant: &str,
+        default_params: EvalParams,
+        default_depth: Option<u8>
+    ) -> BasicAi<MediumBasicBoardState> {
+        let mut ai = BasicAi::with_tt_size(logic, self.tt_size_mb.unwrap_or(DEFAULT_TT_SIZE_MB));
+        ai.eval_params = self.params.as_ref()
+            .and_then(|p| p.get(variant).copied())
+            .unwrap_or(default_params);
+        ai.max_depth = self.max_depth.or(default_depth);
+        if let Some(nmp) = self.null_move_pruning {
+            ai.null_move_pruning = nmp;
+        }
+        if let Some(lmr) = self.late_move_reductions {
+            ai.late_move_reductions = lmr;
+        }
+        if let Some(qdepth) = self.quiescence_depth {
+            ai.quiescence_depth = qdepth;
+        }
+        ai
+    }
+}
+
+struct Args {
+    a: EngineConfig,
+    b: EngineConfig,
+    games: usize,
+    time_to_play: Duration,
+    depth: Option<u8>,
+    variant: Option<String>,
+}
+
+fn parse_args() -> Args {
+    let mut args = Args {
+        a: EngineConfig::default(),
+        b: EngineConfig::default(),
+        games: 20,
+        time_to_play: Duration::from_millis(100),
+        depth: None,
+        variant: None,
+    };
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().unwrap_or_else(|| panic!("Missing value for {arg}"));
+        match arg.as_str() {
+            "--a" => args.a = EngineConfig::parse(&value()),
+            "--b" => args.b = EngineConfig::parse(&value()),
+            "--games" => args.games = value().parse().expect("Number of games should be a number"),
+            "--ms" => args.time_to_play = Duration::from_millis(
+                value().parse().expect("Time per play should be a whole number of milliseconds")
+            ),
+            "--depth" => args.depth = Some(value().parse().expect("Depth should be a number")),
+            "--variant" => args.variant = Some(value()),
+            other => panic!("Unrecognised argument: {other}")
+        }
+    }
+    args
+}
+
+/// Results of games from the perspective of engine A.
+#[derive(Default, Clone, Copy)]
+struct Tally {
+    wins: u32,
+    losses: u32,
+    draws: u32,
+    total_plays: usize,
+}
+
+impl Tally {
+    fn add(&mut self, other: Tally) {
+        self.wins += other.wins;
+        self.losses += other.losses;
+        self.draws += other.draws;
+        self.total_plays += other.total_plays;
+    }
+
+    fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    /// Estimated Elo difference between A and B, and the half-width of its 95% confidence
+    /// interval, or `None` if no games were played.
+    fn elo(&self) -> Option<(f64, f64)> {
+        if self.games() == 0 {
+            return None
+        }
+        let n = self.games() as f64;
+        let score = (self.wins as f64 + self.draws as f64 / 2.0) / n;
+        let (low, high) = score_interval(score, n);
+        let elo = elo_from_score(score, n);
+        let error = (elo_from_score(high, n) - elo_from_score(low, n)) / 2.0;
+        Some((elo, error))
+    }
+}
+
+/// The 95% Wilson score interval for an expected score (between 0 and 1) observed over `games`
+/// games. Unlike an interval based on the observed variance, it doesn't shrink to nothing when
+/// every game has the same result.
+fn score_interval(score: f64, games: f64) -> (f64, f64) {
+    const Z: f64 = 1.96;
+    let z2 = Z * Z;
+    let centre = (score + z2 / (2.0 * games)) / (1.0 + z2 / games);
+    let half_width = Z / (1.0 + z2 / games)
+        * (score * (1.0 - score) / games + z2 / (4.0 * games * games)).sqrt();
+    (centre - half_width, centre + half_width)
+}
+
+/// The Elo difference implied by an expected score (between 0 and 1) over `games` games. A score of
+/// 0 or 1 would imply an infinite difference, so the score is kept half a game away from either.
+fn elo_from_score(score: f64, games: f64) -> f64 {
+    let bound = 1.0 / (2.0 * games);
+    let score = score.clamp(bound, 1.0 - bound);
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+fn print_tally(label: &str, tally: &Tally) {
+    let Some((elo, error)) = tally.elo() else {
+        // No games were played, so there is nothing to average
+        println!("{label:<12} {:>5} {:>5} {:>5} {:>9} {:>8}", 0, 0, 0, "n/a", "n/a");
+        return
+    };
+    println!(
+        "{:<12} {:>5} {:>5} {:>5} {:>9.1} {:>8.1} ± {:.1}",
+        label,
+        tally.wins,
+        tally.losses,
+        tally.draws,
+        tally.total_plays as f64 / tally.games() as f64,
+        elo,
+        error
+    );
+}
+
+fn main() {
+    let args = parse_args();
+    let time_to_play = if args.depth.is_some() { FIXED_DEPTH_TIME } else { args.time_to_play };
+    let n_pairs = args.games.div_ceil(2);
+    let mut rng = rand::thread_rng();
+
+    println!("{:<12} {:>5} {:>5} {:>5} {:>9} {:>8}", "Variant", "W", "L", "D", "Avg plays",
+             "Elo (A - B)");
+    let mut total = Tally::default();
+    for (rules, board, name, eval_params) in OOTB_VARIANTS {
+        if args.variant.as_ref().is_some_and(|v| v.as_str() != name) {
+            continue
+        }
+        let logic = MediumBasicGame::new(rules, board).expect("Could not construct game").logic;
+        let mut ai_a = args.a.build(logic, name, eval_params, args.depth);
+        let mut ai_b = args.b.build(logic, name, eval_params, args.depth);
+        let mut tally = Tally::default();
+        for _ in 0..n_pairs {
+            // Both games of a pair use the same seed, so begin with the same random plays
+            let seed: u64 = rng.gen();
+            for a_side in [Side::Attacker, Side::Defender] {
+                let game = MediumBasicGame::new(rules, board).expect("Could not construct game");
+                let mut pair_rng = StdRng::seed_from_u64(seed);
+                let record = if a_side == Side::Attacker {
+                    play_game(game, &mut ai_a, &mut ai_b, time_to_play, MAX_PLAYS, RANDOM_PLIES,
+                              &mut pair_rng)
+                } else {
+                    play_game(game, &mut ai_b, &mut ai_a, time_to_play, MAX_PLAYS, RANDOM_PLIES,
+                              &mut pair_rng)
+                };
+                match record.winner {
+                    Some(side) if side == a_side => tally.wins += 1,
+                    Some(_) => tally.losses += 1,
+                    None => tally.draws += 1
+                }
+                tally.total_plays += record.n_plays;
+            }
+        }
+        print_tally(name, &tally);
+        total.add(tally);
+    }
+    print_tally("Total", &total);
+}
@@ -0,0 +1,178 @@
+//! Check and benchmark move generation by perft (see `taflr::perft`): count the positions reachable
+//! in a given number of plies from a position of an out-of-the-box variant, and report how many
+//! positions were reached per second.
+//!
+//! With `--divide`, the count after each legal play from the position is also shown. With
+//! `--record`, the counts to each depth up to `--depth` from the starting position of every
+//! out-of-the-box variant are written to the counts file; with `--check`, they are recomputed and
+//! compared with the counts in the file, and the tool exits with an error if any differ. Record the
+//! counts on a build whose move generation is trusted, and check them after changing it.
+//!
+//! Usage: `taflr-perft [--variant <name>] [--fen <board>] [--side <attackers|defenders>]
+//! [--depth <n>] [--divide] [--record | --check] [--counts <file>]`
+//!
+//! `--side` sets the side to play in the position; without it, the position's own side to play is
+//! kept.
+
+use hnefatafl::aliases::MediumBasicGame;
+use hnefatafl::pieces::Side;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use taflr::engine_protocol::parse_side;
+use taflr::perft::{divide, perft};
+use taflr::variants::OOTB_VARIANTS;
+
+/// Known-good counts for each out-of-the-box variant, by variant name: the perft counts from the
+/// starting position to depths 1, 2, and so on.
+type Counts = BTreeMap<String, Vec<u64>>;
+
+enum Mode {
+    /// Count positions from the given position.
+    Count,
+    /// Write the counts file.
+    Record,
+    /// Compare counts with the counts file.
+    Check,
+}
+
+struct Args {
+    variant: String,
+    fen: Option<String>,
+    side: Option<Side>,
+    depth: usize,
+    divide: bool,
+    mode: Mode,
+    counts: PathBuf,
+}
+
+fn parse_args() -> Args {
+    let mut args = Args {
+        variant: "Copenhagen".to_string(),
+        fen: None,
+        side: None,
+        depth: 3,
+        divide: false,
+        mode: Mode::Count,
+        counts: PathBuf::from("perft_counts.json"),
+    };
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().unwrap_or_else(|| panic!("Missing value for {arg}"));
+        match arg.as_str() {
+            "--variant" => args.variant = value(),
+            "--fen" => args.fen = Some(value()),
+            "--side" => args.side = Some(parse_side(&value()).unwrap_or_else(|e| panic!("{e}"))),
+            "--depth" => args.depth = value().parse().expect("Depth should be a number"),
+            "--divide" => args.divide = true,
+            "--record" => args.mode = Mode::Record,
+            "--check" => args.mode = Mode::Check,
+            "--counts" => args.counts = PathBuf::from(value()),
+            other => panic!("Unrecognised argument: {other}")
+        }
+    }
+    args
+}
+
+/// Positions reached per second.
+fn nodes_per_sec(nodes: u64, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 { nodes as f64 / secs } else { 0.0 }
+}
+
+/// Construct a game of the out-of-the-box variant called `name`, from `fen` if given or else from
+/// the variant's starting position.
+fn new_game(name: &str, fen: Option<&str>) -> MediumBasicGame {
+    let Some((rules, board, _, _)) = OOTB_VARIANTS.into_iter().find(|(_, _, n, _)| *n == name)
+    else {
+        panic!("Unknown variant: {name}")
+    };
+    MediumBasicGame::new(rules, fen.unwrap_or(board)).expect("Could not construct game")
+}
+
+/// Count the positions to `depth` from `game`, timing the count.
+fn timed_perft(game: &MediumBasicGame, depth: usize) -> (u64, Duration) {
+    let start_time = Instant::now();
+    let mut posn_history = game.position_history.clone();
+    let nodes = perft(&game.logic, game.state, depth, &mut posn_history);
+    (nodes, start_time.elapsed())
+}
+
+fn count(args: &Args) {
+    let mut game = new_game(&args.variant, args.fen.as_deref());
+    if let Some(side) = args.side {
+        game.state.side_to_play = side;
+        // The history must end with the position being counted from
+        game.position_history = vec![(&game.state).into()];
+    }
+    if args.divide {
+        let start_time = Instant::now();
+        let mut posn_history = game.position_history.clone();
+        let counts = divide(&game.logic, game.state, args.depth, &mut posn_history);
+        let elapsed = start_time.elapsed();
+        for (play, nodes) in &counts {
+            println!("{}: {nodes}", play.play);
+        }
+        let nodes = counts.iter().map(|(_, n)| n).sum();
+        println!("{} plays, {nodes} positions in {:.2}s ({:.0} positions/s)", counts.len(),
+                 elapsed.as_secs_f64(), nodes_per_sec(nodes, elapsed));
+    } else {
+        for depth in 1..=args.depth {
+            let (nodes, elapsed) = timed_perft(&game, depth);
+            println!("Depth {depth}: {nodes} positions in {:.2}s ({:.0} positions/s)",
+                     elapsed.as_secs_f64(), nodes_per_sec(nodes, elapsed));
+        }
+    }
+}
+
+fn record(args: &Args) {
+    let mut counts = Counts::new();
+    for (_, _, name, _) in OOTB_VARIANTS {
+        let game = new_game(name, None);
+        let variant_counts = (1..=args.depth)
+            .map(|depth| timed_perft(&game, depth).0)
+            .collect::<Vec<_>>();
+        println!("{name}: {variant_counts:?}");
+        counts.insert(name.to_string(), variant_counts);
+    }
+    let json = serde_json::to_string_pretty(&counts).expect("Could not serialise counts");
+    std::fs::write(&args.counts, json).expect("Could not write counts");
+    println!("Wrote counts to {}", args.counts.display());
+}
+
+/// Returns whether every count matched.
+fn check(args: &Args) -> bool {
+    let json = std::fs::read_to_string(&args.counts).unwrap_or_else(|e| {
+        panic!("Could not read counts from {}: {e}", args.counts.display())
+    });
+    let counts: Counts = serde_json::from_str(&json).expect("Bad counts file");
+    let mut ok = true;
+    for (_, _, name, _) in OOTB_VARIANTS {
+        let Some(expected) = counts.get(name) else {
+            println!("{name}: no counts recorded");
+            ok = false;
+            continue
+        };
+        let game = new_game(name, None);
+        for (depth, &expected) in (1..).zip(expected.iter()) {
+            let (nodes, elapsed) = timed_perft(&game, depth);
+            let result = if nodes == expected { "ok" } else { "MISMATCH" };
+            println!("{name} depth {depth}: {nodes} (expected {expected}) {result} \
+                      ({:.0} positions/s)", nodes_per_sec(nodes, elapsed));
+            ok &= nodes == expected;
+        }
+    }
+    ok
+}
+
+fn main() {
+    let args = parse_args();
+    match args.mode {
+        Mode::Count => count(&args),
+        Mode::Record => record(&args),
+        Mode::Check => if !check(&args) {
+            eprintln!("Perft counts differ from those in {}", args.counts.display());
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Generate an endgame tablebase for `BasicAi`, covering every position of an out-of-the-box
+//! variant with a king and up to the given numbers of attacking and defending soldiers.
+//!
+//! Runs headless, and writes the tablebase to the file from which the app loads it when a game of
+//! the variant starts. The number of positions grows very quickly with the number of pieces, so
+//! keep them low (especially on larger boards).
+//!
+//! Usage: `taflr-tablebase [--variant <name>] [--attackers <n>] [--defenders <n>]
+//! [--threads <n>] [--out <file>]`
+
+use hnefatafl::aliases::MediumBasicGame;
+use std::path::PathBuf;
+use std::time::Instant;
+use taflr::ai::{default_search_threads, tablebase_path, Tablebase, TABLEBASE_DIR};
+use taflr::variants::OOTB_VARIANTS;
+
+struct Args {
+    variant: String,
+    attackers: u8,
+    defenders: u8,
+    threads: usize,
+    out: Option<PathBuf>,
+}
+
+fn parse_args() -> Args {
+    let mut args = Args {
+        variant: "Brandubh".to_string(),
+        attackers: 2,
+        defenders: 1,
+        threads: default_search_threads(),
+        out: None,
+    };
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().unwrap_or_else(|| panic!("Missing value for {arg}"));
+        match arg.as_str() {
+            "--variant" => args.variant = value(),
+            "--attackers" => args.attackers = value().parse()
+                .expect("Number of attackers should be a number"),
+            "--defenders" => args.defenders = value().parse()
+                .expect("Number of defenders should be a number"),
+            "--threads" => args.threads = value().parse()
+                .expect("Number of threads should be a number"),
+            "--out" => args.out = Some(PathBuf::from(value())),
+            other => panic!("Unrecognised argument: {other}")
+        }
+    }
+    args
+}
+
+fn main() {
+    let args = parse_args();
+    let Some((rules, board, name, _)) = OOTB_VARIANTS.into_iter()
+        .find(|(_, _, name, _)| *name == args.variant) else {
+        panic!("Unknown variant: {}", args.variant)
+    };
+    let logic = MediumBasicGame::new(rules, board).expect("Could not construct game").logic;
+
+    println!("{name}: solving positions with up to {} attackers and {} defenders...",
+             args.attackers, args.defenders);
+    let start_time = Instant::now();
+    let tb = Tablebase::generate(
+        logic,
+        args.attackers,
+        args.defenders,
+        args.threads,
+        &mut |attackers, defenders, positions, decided| {
+            println!("{attackers} attackers, {defenders} defenders: {positions} positions, \
+                      {decided} won or lost ({:.1}s)", start_time.elapsed().as_secs_f32());
+        }
+    );
+
+    let out = args.out.unwrap_or_else(|| {
+        std::fs::create_dir_all(TABLEBASE_DIR).expect("Could not create tablebase directory");
+        tablebase_path(name)
+    });
+    tb.save(&out).expect("Could not write tablebase");
+    println!("Wrote {} positions to {}", tb.len(), out.display());
+}
@@ -0,0 +1,185 @@
+//! Tune the weights used by `BasicAi` to evaluate positions, by playing quick games of each
+//! out-of-the-box variant between two `BasicAi`s and then adjusting the weights to minimise the
+//! error with which the evaluation of each position in those games predicts the game's outcome
+//! ("Texel tuning").
+//!
+//! Runs headless, and writes the tuned parameters to a JSON file which the app loads on startup.
+//!
+//! Usage: `taflr-tune [--games <n>] [--ms <per play>] [--variant <name>] [--out <file>]`
+
+use hnefatafl::aliases::{MediumBasicBoardState, MediumBasicGame};
+use hnefatafl::game::state::GameState;
+use hnefatafl::pieces::Side::Attacker;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use taflr::ai::{save_tuned_params, BasicAi, EvalParams, N_EVAL_PARAMS, TUNED_PARAMS_FILE};
+use taflr::selfplay::play_game;
+use taflr::variants::OOTB_VARIANTS;
+
+/// Size of each AI's transposition table. Games are quick, so there is no need for a large one.
+const TT_SIZE_MB: usize = 16;
+
+/// Number of random plays at the start of each game, so that games differ from each other.
+const RANDOM_PLIES: usize = 4;
+
+/// Games that haven't ended after this many plays are treated as draws.
+const MAX_PLAYS: usize = 300;
+
+/// Maximum number of passes over all the weights when fitting.
+const MAX_PASSES: usize = 100;
+
+struct Args {
+    games: usize,
+    time_to_play: Duration,
+    variant: Option<String>,
+    out: PathBuf,
+}
+
+fn parse_args() -> Args {
+    let mut args = Args {
+        games: 1000,
+        time_to_play: Duration::from_millis(10),
+        variant: None,
+        out: PathBuf::from(TUNED_PARAMS_FILE),
+    };
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().unwrap_or_else(|| panic!("Missing value for {arg}"));
+        match arg.as_str() {
+            "--games" => args.games = value().parse().expect("Number of games should be a number"),
+            "--ms" => args.time_to_play = Duration::from_millis(
+                value().parse().expect("Time per play should be a whole number of milliseconds")
+            ),
+            "--variant" => args.variant = Some(value()),
+            "--out" => args.out = PathBuf::from(value()),
+            other => panic!("Unrecognised argument: {other}")
+        }
+    }
+    args
+}
+
+/// A position from a self-play game, and the result of that game from the attacker's perspective
+/// (1 for a win, 0.5 for a draw, 0 for a loss).
+type Sample = (GameState<MediumBasicBoardState>, f64);
+
+/// Convert a score into the expected result, from the attacker's perspective, using a logistic
+/// function scaled by `k`.
+fn expected_result(score: i32, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score as f64 / 400.0))
+}
+
+/// Mean squared error between the results predicted by `evaluator` and the actual results.
+fn error(evaluator: &BasicAi<MediumBasicBoardState>, samples: &[Sample], k: f64) -> f64 {
+    samples.iter()
+        .map(|(state, result)| (result - expected_result(evaluator.evaluate(state), k)).powi(2))
+        .sum::<f64>() / samples.len() as f64
+}
+
+/// Find the scaling constant which minimises the error with the evaluator's current weights, so
+/// that the fitted weights stay on the same scale as the originals.
+fn fit_k(evaluator: &BasicAi<MediumBasicBoardState>, samples: &[Sample]) -> f64 {
+    (0..40)
+        .map(|i| 0.05 * 1.2f64.powi(i))
+        .map(|k| (k, error(evaluator, samples, k)))
+        .min_by(|(_, e1), (_, e2)| e1.total_cmp(e2))
+        .map(|(k, _)| k)
+        .expect("No candidate values of k")
+}
+
+/// Adjust each weight in turn, keeping any change which reduces the error, until no change helps.
+/// Each weight's step size starts at around a quarter of its value and halves whenever neither
+/// increasing nor decreasing the weight helps.
+fn fit_params(
+    evaluator: &mut BasicAi<MediumBasicBoardState>,
+    samples: &[Sample],
+    k: f64
+) -> EvalParams {
+    let mut weights = evaluator.eval_params.to_array();
+    let mut steps = weights.map(|w| (w.abs() / 4).max(1));
+    let mut best_error = error(evaluator, samples, k);
+    for pass in 0..MAX_PASSES {
+        let mut improved = false;
+        for i in 0..N_EVAL_PARAMS {
+            let mut improved_weight = false;
+            for delta in [steps[i], -steps[i]] {
+                let mut candidate = weights;
+                candidate[i] += delta;
+                evaluator.eval_params = EvalParams::from_array(candidate);
+                let e = error(evaluator, samples, k);
+                if e < best_error {
+                    best_error = e;
+                    weights = candidate;
+                    improved_weight = true;
+                    break
+                }
+            }
+            if improved_weight {
+                improved = true;
+            } else {
+                // No improvement in either direction, so try a finer adjustment next time
+                steps[i] = (steps[i] / 2).max(1);
+            }
+        }
+        evaluator.eval_params = EvalParams::from_array(weights);
+        println!("  Pass {}: error {:.6}", pass + 1, best_error);
+        if !improved && steps.iter().all(|s| *s == 1) {
+            break
+        }
+    }
+    EvalParams::from_array(weights)
+}
+
+fn main() {
+    let args = parse_args();
+    let mut rng = rand::thread_rng();
+    let mut tuned = HashMap::new();
+
+    for (rules, board, name, eval_params) in OOTB_VARIANTS {
+        if args.variant.as_ref().is_some_and(|v| v.as_str() != name) {
+            continue
+        }
+        println!("{name}: playing {} games...", args.games);
+        let game = MediumBasicGame::new(rules, board).expect("Could not construct game");
+        let mut attacker = BasicAi::with_tt_size(game.logic, TT_SIZE_MB);
+        let mut defender = BasicAi::with_tt_size(game.logic, TT_SIZE_MB);
+        attacker.eval_params = eval_params;
+        defender.eval_params = eval_params;
+
+        let mut samples: Vec<Sample> = Vec::new();
+        let (mut att_wins, mut def_wins, mut draws) = (0, 0, 0);
+        for _ in 0..args.games {
+            let record = play_game(
+                MediumBasicGame::new(rules, board).expect("Could not construct game"),
+                &mut attacker,
+                &mut defender,
+                args.time_to_play,
+                MAX_PLAYS,
+                RANDOM_PLIES,
+                &mut rng
+            );
+            let result = match record.winner {
+                Some(Attacker) => { att_wins += 1; 1.0 },
+                Some(_) => { def_wins += 1; 0.0 },
+                None => { draws += 1; 0.5 }
+            };
+            samples.extend(record.states.into_iter().map(|s| (s, result)));
+        }
+        println!("{name}: {att_wins} attacker wins, {def_wins} defender wins, {draws} draws, \
+                  {} positions", samples.len());
+
+        let mut evaluator = BasicAi::with_tt_size(game.logic, 1);
+        evaluator.eval_params = eval_params;
+        let k = fit_k(&evaluator, &samples);
+        println!("{name}: fitting weights (k = {k:.3})...");
+        let params = fit_params(&mut evaluator, &samples, k);
+        for (param_name, (old, new)) in EvalParams::NAMES.iter()
+            .zip(eval_params.to_array().into_iter().zip(params.to_array())) {
+            println!("  {param_name:<24} {old:>6} -> {new:>6}");
+        }
+        tuned.insert(name.to_string(), params);
+    }
+
+    save_tuned_params(&args.out, &tuned).expect("Could not write tuned parameters");
+    println!("Wrote tuned parameters to {}", args.out.display());
+}
@@ -1,5 +1,6 @@
 use dioxus::prelude::*;
 use hnefatafl::pieces::Side;
+use crate::aictrl::delete_tt_snapshot;
 use crate::components::header_bar::HeaderBar;
 use crate::components::navbutton::NavButton;
 use crate::message::error_msg;
@@ -83,7 +84,10 @@ pub(crate) fn LoadGame() -> Element {
         if let Some(id) = id_opt {
             spawn(async move {
                 match db_ctrl.delete_game_from_db(id).await {
-                    Ok(_) => saved_games.write().retain(|sg| sg.id != id),
+                    Ok(_) => {
+                        saved_games.write().retain(|sg| sg.id != id);
+                        delete_tt_snapshot(id);
+                    },
                     Err(e) => error_msg(format!("Failed to delete saved game from database: {e:?}").as_str())
                 };
 
@@ -14,6 +14,7 @@ pub(crate) fn MainMenu() -> Element {
             class: "mainmenu",
             NavButton { route: Route::NewGame, class: "mainmenu-item", text: "New Game" }
             NavButton { route: Route::LoadGame, class: "mainmenu-item", text: "Load Game" }
+            NavButton { route: Route::Settings, class: "mainmenu-item", text: "Settings" }
             NavButton { route: Route::About, class: "mainmenu-item", text: "About" }
             NavButton { route: Route::Quit, class: "mainmenu-item", text: "Quit"}
             button {
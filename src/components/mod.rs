@@ -7,6 +7,7 @@ mod navbutton;
 mod load_game;
 mod header_bar;
 mod status_bar;
+mod settings;
 
 use dioxus::prelude::{GlobalSignal, Signal};
 use crate::game_settings::GameSettings;
@@ -17,6 +18,7 @@ pub(crate) use about::About;
 pub(crate) use mainmenu::MainMenu;
 pub(crate) use play_game::PlayGame;
 pub(crate) use load_game::LoadGame;
+pub(crate) use settings::Settings;
 
 pub(crate) use status_bar::StatusBar;
 
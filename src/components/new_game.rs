@@ -6,7 +6,8 @@ use hnefatafl::preset;
 use crate::components::header_bar::HeaderBar;
 use crate::game_settings::GameSettings;
 use crate::error::DbError;
-use crate::gamectrl::Player;
+use crate::ai::Difficulty;
+use crate::gamectrl::{AiEngine, ExternalEngine, Player};
 use crate::route::Route;
 use crate::sqlite::DbController;
 use crate::variants::Variant;
@@ -91,10 +92,18 @@ pub(crate) fn GameSetup() -> Element {
     let mut attacker_name = use_signal(|| att_name.to_string());
     let mut attacker_type = use_signal(|| PlayerType::Human);
     let mut attacker_ai_time = use_signal(|| 5u32);
+    let mut attacker_ai_engine = use_signal(AiEngine::default);
+    let mut attacker_ai_level = use_signal(Difficulty::default);
+    let mut attacker_engine_path = use_signal(String::new);
+    let mut attacker_engine_args = use_signal(String::new);
 
     let mut defender_name = use_signal(|| def_name.to_string());
     let mut defender_type = use_signal(|| PlayerType::Human);
     let mut defender_ai_time = use_signal(|| 5u32);
+    let mut defender_ai_engine = use_signal(AiEngine::default);
+    let mut defender_ai_level = use_signal(Difficulty::default);
+    let mut defender_engine_path = use_signal(String::new);
+    let mut defender_engine_args = use_signal(String::new);
 
     let start_game = move |_: MouseEvent| async move {
         let attacker = Player {
@@ -103,6 +112,12 @@ pub(crate) fn GameSetup() -> Element {
                 Some(Duration::from_secs(*attacker_ai_time.read().deref() as u64))
             } else {
                 None
+            },
+            ai_engine: *attacker_ai_engine.read(),
+            ai_level: *attacker_ai_level.read(),
+            external_engine: ExternalEngine {
+                path: attacker_engine_path.read().trim().to_string(),
+                args: attacker_engine_args.read().trim().to_string()
             }
         };
         let defender = Player {
@@ -111,6 +126,12 @@ pub(crate) fn GameSetup() -> Element {
                 Some(Duration::from_secs(*defender_ai_time.read().deref() as u64))
             } else {
                 None
+            },
+            ai_engine: *defender_ai_engine.read(),
+            ai_level: *defender_ai_level.read(),
+            external_engine: ExternalEngine {
+                path: defender_engine_path.read().trim().to_string(),
+                args: defender_engine_args.read().trim().to_string()
             }
         };
         let settings = GameSettings {
@@ -227,6 +248,92 @@ pub(crate) fn GameSetup() -> Element {
                         }
 
                         if attacker_type.read().deref() == &PlayerType::AI {
+                            div {
+                                class: "form-group",
+
+                                label {
+                                    class: "form-label",
+                                    "AI Engine:"
+                                }
+
+                                select {
+                                    class: "form-select",
+                                    onchange: move |e| {
+                                        if let Ok(engine) = e.value().parse() {
+                                            attacker_ai_engine.set(engine);
+                                        }
+                                    },
+                                    for engine in AiEngine::available().iter().copied() {
+                                        option {
+                                            value: "{engine}",
+                                            selected: engine == *attacker_ai_engine.read(),
+                                            "{engine.label()}"
+                                        }
+                                    }
+                                }
+                            }
+
+                            if *attacker_ai_engine.read() == AiEngine::AlphaBeta {
+                                div {
+                                    class: "form-group",
+
+                                    label {
+                                        class: "form-label",
+                                        "AI Level:"
+                                    }
+
+                                    select {
+                                        class: "form-select",
+                                        onchange: move |e| {
+                                            if let Ok(level) = e.value().parse() {
+                                                attacker_ai_level.set(level);
+                                            }
+                                        },
+                                        for level in Difficulty::ALL {
+                                            option {
+                                                value: "{level}",
+                                                selected: level == *attacker_ai_level.read(),
+                                                "{level.label()}"
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+
+                            if *attacker_ai_engine.read() == AiEngine::External {
+                                div {
+                                    class: "form-group",
+
+                                    label {
+                                        class: "form-label",
+                                        "Engine Path:"
+                                    }
+
+                                    input {
+                                        class: "form-input",
+                                        r#type: "text",
+                                        value: "{attacker_engine_path}",
+                                        oninput: move |e| attacker_engine_path.set(e.value())
+                                    }
+                                }
+
+                                div {
+                                    class: "form-group",
+
+                                    label {
+                                        class: "form-label",
+                                        "Engine Arguments:"
+                                    }
+
+                                    input {
+                                        class: "form-input",
+                                        r#type: "text",
+                                        value: "{attacker_engine_args}",
+                                        oninput: move |e| attacker_engine_args.set(e.value())
+                                    }
+                                }
+                            }
+
                             div {
                                 class: "form-group",
 
@@ -296,6 +403,92 @@ pub(crate) fn GameSetup() -> Element {
                         }
 
                         if defender_type.read().deref() == &PlayerType::AI {
+                            div {
+                                class: "form-group",
+
+                                label {
+                                    class: "form-label",
+                                    "AI Engine:"
+                                }
+
+                                select {
+                                    class: "form-select",
+                                    onchange: move |e| {
+                                        if let Ok(engine) = e.value().parse() {
+                                            defender_ai_engine.set(engine);
+                                        }
+                                    },
+                                    for engine in AiEngine::available().iter().copied() {
+                                        option {
+                                            value: "{engine}",
+                                            selected: engine == *defender_ai_engine.read(),
+                                            "{engine.label()}"
+                                        }
+                                    }
+                                }
+                            }
+
+                            if *defender_ai_engine.read() == AiEngine::AlphaBeta {
+                                div {
+                                    class: "form-group",
+
+                                    label {
+                                        class: "form-label",
+                                        "AI Level:"
+                                    }
+
+                                    select {
+                                        class: "form-select",
+                                        onchange: move |e| {
+                                            if let Ok(level) = e.value().parse() {
+                                                defender_ai_level.set(level);
+                                            }
+                                        },
+                                        for level in Difficulty::ALL {
+                                            option {
+                                                value: "{level}",
+                                                selected: level == *defender_ai_level.read(),
+                                                "{level.label()}"
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+
+                            if *defender_ai_engine.read() == AiEngine::External {
+                                div {
+                                    class: "form-group",
+
+                                    label {
+                                        class: "form-label",
+                                        "Engine Path:"
+                                    }
+
+                                    input {
+                                        class: "form-input",
+                                        r#type: "text",
+                                        value: "{defender_engine_path}",
+                                        oninput: move |e| defender_engine_path.set(e.value())
+                                    }
+                                }
+
+                                div {
+                                    class: "form-group",
+
+                                    label {
+                                        class: "form-label",
+                                        "Engine Arguments:"
+                                    }
+
+                                    input {
+                                        class: "form-input",
+                                        r#type: "text",
+                                        value: "{defender_engine_args}",
+                                        oninput: move |e| defender_engine_args.set(e.value())
+                                    }
+                                }
+                            }
+
                             div {
                                 class: "form-group",
 
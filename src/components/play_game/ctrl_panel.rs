@@ -1,6 +1,7 @@
 use dioxus::prelude::*;
 use hnefatafl::aliases::{MediumBasicBoardState, MediumPlayRecord};
 use hnefatafl::pieces::Side;
+use crate::aictrl::AI_PROGRESS;
 use crate::components::navbutton::NavButton;
 use crate::gamectrl::GameController;
 use crate::route::Route;
@@ -91,6 +92,76 @@ fn PlayHistory(plays: Vec<MediumPlayRecord>) -> Element {
     }
 }
 
+/// Format a (potentially large) count compactly, eg, 123456 as "123k".
+fn fmt_count(n: u64) -> String {
+    if n >= 1_000_000 {
+        format!("{:.1}M", n as f64 / 1_000_000.0)
+    } else if n >= 1_000 {
+        format!("{}k", n / 1_000)
+    } else {
+        n.to_string()
+    }
+}
+
+/// Indicate that the AI is thinking, and how far its search has progressed.
+#[component]
+fn AiThinking() -> Element {
+    let progress_str = AI_PROGRESS.read()
+        .map(|p| format!("Thinking… depth {}, {} nodes", p.depth, fmt_count(p.nodes)));
+    rsx! {
+        if let Some(s) = progress_str {
+            div {
+                class: "ai-thinking",
+                "{s}"
+            }
+        }
+    }
+}
+
+/// Display the line of play the AI expects, following its most recent play.
+#[component]
+fn AiExpects() -> Element {
+    let game_ctrl = use_context::<GameController<MediumBasicBoardState>>();
+    let pv_str = game_ctrl.last_search.read().as_ref()
+        .map(|info| info.principal_variation()
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<String>>()
+            .join(" "))
+        .filter(|s| !s.is_empty());
+    rsx! {
+        if let Some(pv) = pv_str {
+            div {
+                class: "ai-expects",
+                "AI expects: {pv}"
+            }
+        }
+    }
+}
+
+/// Indicate that the AI's most recent search found a forced win for one side.
+#[component]
+fn ForcedWin() -> Element {
+    let game_ctrl = use_context::<GameController<MediumBasicBoardState>>();
+    let side_to_play = game_ctrl.game.read().state.side_to_play;
+    let win_str = game_ctrl.last_search.read().as_ref()
+        .and_then(|info| info.forced_win())
+        .map(|(winner, n)| {
+            // The count includes the play the AI has since made, if the AI is the winner.
+            let n = if winner == side_to_play { n } else { n.saturating_sub(1) };
+            let winner = if winner == Side::Attacker { "Attacker" } else { "Defender" };
+            format!("{winner} wins in {n}")
+        });
+    rsx! {
+        if let Some(s) = win_str {
+            div {
+                class: "ai-forced-win",
+                "{s}"
+            }
+        }
+    }
+}
+
 #[component]
 pub(crate) fn ControlPanel() -> Element {
 
@@ -129,6 +200,9 @@ pub(crate) fn ControlPanel() -> Element {
                     "Undo"
                 }
             }
+            AiThinking {}
+            AiExpects {}
+            ForcedWin {}
             PlayHistory { plays: play_history }
         }
     }
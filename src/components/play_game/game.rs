@@ -7,7 +7,7 @@ use async_std::prelude::StreamExt;
 use dioxus::prelude::*;
 use hnefatafl::aliases::MediumBasicBoardState;
 use hnefatafl::game::GameStatus;
-use crate::aictrl::{compute_ai_play, AiRequest};
+use crate::aictrl::{cancel_ai_play, compute_ai_play, save_tt_snapshot, AiRequest};
 use crate::components;
 use crate::components::play_game::board::Board;
 use crate::components::play_game::ctrl_panel::ControlPanel;
@@ -42,13 +42,19 @@ pub(crate) fn GameView(settings: GameSettings, game: HnGame<MediumBasicBoardStat
             // Compute AI move in background
             let response = compute_ai_play(request).await;
             match response {
-                Ok(resp) => {
+                Ok(Some(resp)) => {
                     if let Some(ai_move) = game_ctrl.handle_ai_response(resp) {
-                        if let Err(e) = game_ctrl.apply_play(ai_move.play) {
-                            warning_msg(format!("AI gave invalid play: {e:?}").as_str())
+                        match game_ctrl.apply_play(ai_move.play) {
+                            Ok(_) => {
+                                #[cfg(not(target_arch = "wasm32"))]
+                                game_ctrl.ponder();
+                            },
+                            Err(e) => warning_msg(format!("AI gave invalid play: {e:?}").as_str())
                         }
                     }
                 },
+                // Search was cancelled, so there is nothing to play
+                Ok(None) => {},
                 Err(e) => error_msg(format!("Error: {e}").as_str())
             }
         }
@@ -56,19 +62,36 @@ pub(crate) fn GameView(settings: GameSettings, game: HnGame<MediumBasicBoardStat
 
     use_context_provider(|| ai_coroutine);
 
+    // Don't leave the AI thinking about a game that is no longer being displayed
+    use_drop(move || {
+        cancel_ai_play();
+        save_tt_snapshot(db_id);
+    });
+
     use_effect(|| {
         let game_ctrl = use_context::<GameController<MediumBasicBoardState>>();
 
+        let engine = game_ctrl.current_player().ai_engine;
+        let difficulty = game_ctrl.current_player().ai_level;
+        let external_engine = game_ctrl.current_player().external_engine.clone();
         if let Some(time_to_play) = game_ctrl.current_player().ai_play_time {
             let game_state = game_ctrl.game.read().state;
             let posn_history = game_ctrl.game.read().position_history.clone();
+            let plays = game_ctrl.game.read().play_history.iter().map(|pr| pr.play).collect();
             if game_state.status == GameStatus::Ongoing {
                 use_context::<Coroutine<AiRequest<MediumBasicBoardState>>>().send(AiRequest {
                     game_state,
                     posn_history,
-                    time_to_play
+                    plays,
+                    time_to_play,
+                    engine,
+                    difficulty,
+                    external_engine
                 })
             };
+        } else if game_ctrl.game.read().state.status != GameStatus::Ongoing {
+            // The AI may be pondering a reply to the play that ended the game
+            cancel_ai_play();
         }
 
         let action_opt = game_ctrl.last_action.read().as_ref().copied();
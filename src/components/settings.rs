@@ -0,0 +1,138 @@
+use std::ops::Deref;
+use dioxus::prelude::*;
+use crate::app_settings::{AppSettings, APP_SETTINGS, MAX_TT_SIZE_MB, MIN_TT_SIZE_MB};
+use crate::components::header_bar::HeaderBar;
+use crate::message::{error_msg, info_msg};
+use crate::sqlite::DbController;
+
+#[component]
+pub(crate) fn Settings() -> Element {
+    let db_ctrl = use_context::<DbController>();
+    let mut tt_size_mb = use_signal(|| APP_SETTINGS.read().tt_size_mb);
+    let mut search_threads = use_signal(|| APP_SETTINGS.read().search_threads);
+    let mut ponder = use_signal(|| APP_SETTINGS.read().ponder);
+    let mut save_tt = use_signal(|| APP_SETTINGS.read().save_tt);
+
+    let save = move |_: MouseEvent| {
+        let mut db_ctrl = db_ctrl.clone();
+        async move {
+            let settings = AppSettings {
+                tt_size_mb: tt_size_mb.read().clamp(MIN_TT_SIZE_MB, MAX_TT_SIZE_MB),
+                search_threads: *search_threads.read().deref(),
+                ponder: *ponder.read().deref(),
+                save_tt: *save_tt.read().deref(),
+            };
+            match db_ctrl.save_app_settings(&settings).await {
+                Ok(()) => {
+                    *APP_SETTINGS.write() = settings;
+                    info_msg("Settings saved. They will apply to the next game you start or load.");
+                },
+                Err(e) => error_msg(format!("Failed to save settings to database: {e:?}").as_str())
+            }
+        }
+    };
+
+    rsx! {
+        div {
+            class: "main-container",
+
+            HeaderBar {
+                title: "Settings"
+            }
+
+            div {
+                class: "game-setup-container",
+
+                div {
+                    class: "setup-section",
+
+                    div {
+                        class: "form-group",
+
+                        label {
+                            class: "form-label",
+                            "AI Memory (MB):"
+                        }
+
+                        input {
+                            class: "form-input",
+                            r#type: "number",
+                            min: "{MIN_TT_SIZE_MB}",
+                            max: "{MAX_TT_SIZE_MB}",
+                            value: "{tt_size_mb}",
+                            oninput: move |e| {
+                                // Smaller values are allowed while typing, and raised on saving
+                                if let Ok(val) = e.value().parse::<usize>() {
+                                    tt_size_mb.set(val.min(MAX_TT_SIZE_MB));
+                                }
+                            }
+                        }
+                    }
+
+                    // The web build can't start threads
+                    if cfg!(not(target_arch = "wasm32")) {
+                        div {
+                            class: "form-group",
+
+                            label {
+                                class: "form-label",
+                                "AI Threads:"
+                            }
+
+                            input {
+                                class: "form-input",
+                                r#type: "number",
+                                min: "1",
+                                max: "64",
+                                value: "{search_threads}",
+                                oninput: move |e| {
+                                    if let Ok(val) = e.value().parse::<usize>() {
+                                        search_threads.set(val.max(1));
+                                    }
+                                }
+                            }
+                        }
+
+                        div {
+                            class: "form-group",
+
+                            label {
+                                class: "form-label",
+                                "AI Thinks on Your Time:"
+                            }
+
+                            input {
+                                class: "form-checkbox",
+                                r#type: "checkbox",
+                                checked: *ponder.read(),
+                                onchange: move |e| ponder.set(e.checked())
+                            }
+                        }
+
+                        div {
+                            class: "form-group",
+
+                            label {
+                                class: "form-label",
+                                "AI Remembers Analysis Between Sessions:"
+                            }
+
+                            input {
+                                class: "form-checkbox",
+                                r#type: "checkbox",
+                                checked: *save_tt.read(),
+                                onchange: move |e| save_tt.set(e.checked())
+                            }
+                        }
+                    }
+                }
+
+                button {
+                    class: "start-game-btn",
+                    onclick: save,
+                    "Save"
+                }
+            }
+        }
+    }
+}
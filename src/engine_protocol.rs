@@ -0,0 +1,330 @@
+//! The OpenTafl Engine Protocol, through which tafl front-ends talk to engines: plain-text
+//! messages, one per line, written to the engine's stdin, with the engine's replies read from its
+//! stdout. `taflr-engine` uses it to offer `BasicAi` to other programs, and the app uses it to play
+//! against other engines.
+//!
+//! Boards are described in OpenTafl's position notation, which is the same as the FEN used
+//! elsewhere in taflr but with a `/` at each end. Plays use taflr's usual notation (eg, `d1-d3`).
+//! OpenTafl's capture annotations (eg, `d1-d3xd4`) are accepted and ignored.
+//!
+//! Only the messages needed to play and analyse games are supported. Messages that aren't
+//! recognised should be ignored, so that either side can be extended.
+
+use hnefatafl::pieces::Side;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// A message from the front-end to the engine.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Command {
+    /// Sent when the engine starts; the engine replies [`Reply::Hello`].
+    Hello,
+    /// The rules of the game to be played, in OpenTafl's rules notation (see [`rules_string`]).
+    Rules(String),
+    /// The current board (see [`position_string`]).
+    Position(String),
+    /// The side to play in the position last sent, before any plays are made from it.
+    Side(Side),
+    /// Time remaining on each side's clock, in milliseconds, and the length and number of any
+    /// overtime periods.
+    Clock {
+        attacker_ms: u64,
+        defender_ms: u64,
+        overtime_ms: u64,
+        attacker_overtimes: u32,
+        defender_overtimes: u32,
+    },
+    /// A play made by the engine's opponent.
+    Move(String),
+    /// Make a play for the given side; the engine replies [`Reply::Move`]. If no [`Command::Side`]
+    /// was sent and no plays have been made since the position was set up, the given side is
+    /// taken to be the side to play; otherwise, the engine replies [`Reply::Error`] if it isn't
+    /// that side's turn.
+    Play(Side),
+    /// Analyse the current position for the given number of seconds; the engine replies
+    /// [`Reply::Analysis`].
+    Analyze { variations: usize, seconds: u64 },
+    /// The game is over.
+    Finish,
+    /// The engine should exit.
+    Goodbye,
+}
+
+/// A message from the engine to the front-end.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Reply {
+    Hello,
+    /// The play the engine has chosen.
+    Move(String),
+    /// The score of the position, from the perspective of the side to play, and the line of play
+    /// the engine expects.
+    Analysis { score: i32, pv: Vec<String> },
+    /// The engine couldn't do what was asked.
+    Error(String),
+}
+
+/// The name of `side` in messages.
+pub fn side_name(side: Side) -> &'static str {
+    if side == Side::Attacker { "attackers" } else { "defenders" }
+}
+
+/// Parse the name of a side, as written by [`side_name`].
+pub fn parse_side(s: &str) -> Result<Side, String> {
+    match s {
+        "attackers" | "attacker" => Ok(Side::Attacker),
+        "defenders" | "defender" => Ok(Side::Defender),
+        other => Err(format!("Unknown side: {other}"))
+    }
+}
+
+/// The board described by `fen`, in OpenTafl's position notation.
+pub fn position_string(fen: &str) -> String {
+    format!("/{fen}/")
+}
+
+/// The FEN of the board described by `position`, in OpenTafl's position notation.
+pub fn position_fen(position: &str) -> &str {
+    position.trim_matches('/')
+}
+
+/// The rules of a variant in OpenTafl's rules notation: space-separated `key:value` fields. Only
+/// the board size, the variant's name and its starting position are given, so the engine must
+/// already know the variant.
+pub fn rules_string(name: &str, side_len: u8, starting_board: &str) -> String {
+    format!("dim:{side_len} name:{name} start:{}", position_string(starting_board))
+}
+
+/// The value of the field with the given key in `rules`, in OpenTafl's rules notation.
+pub fn rules_field<'a>(rules: &'a str, key: &str) -> Option<&'a str> {
+    rules.split_whitespace()
+        .filter_map(|field| field.split_once(':'))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| v)
+}
+
+/// A play in OpenTafl's notation, without any capture annotation.
+pub fn strip_captures(play: &str) -> &str {
+    play.split('x').next().unwrap_or(play)
+}
+
+/// Parse a value from a clock message, which may be marked with a `*`.
+fn parse_clock_value<T: FromStr>(s: Option<&str>) -> Result<T, String> {
+    let s = s.ok_or("Incomplete clock message")?;
+    s.trim_end_matches('*').parse().map_err(|_| format!("Bad clock value: {s}"))
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim();
+        let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        let mut args = rest.split_whitespace();
+        match name {
+            "hello" => Ok(Command::Hello),
+            "rules" => Ok(Command::Rules(rest.to_string())),
+            "position" => Ok(Command::Position(rest.to_string())),
+            "side" => Ok(Command::Side(parse_side(rest)?)),
+            "clock" => {
+                let attacker_ms = parse_clock_value(args.next())?;
+                let defender_ms = parse_clock_value(args.next())?;
+                // Overtime is optional
+                let overtime_ms = parse_clock_value(args.next()).unwrap_or(0);
+                let attacker_overtimes = parse_clock_value(args.next()).unwrap_or(0);
+                let defender_overtimes = parse_clock_value(args.next()).unwrap_or(0);
+                Ok(Command::Clock {
+                    attacker_ms,
+                    defender_ms,
+                    overtime_ms,
+                    attacker_overtimes,
+                    defender_overtimes
+                })
+            },
+            "move" => Ok(Command::Move(strip_captures(rest).to_string())),
+            "play" => Ok(Command::Play(parse_side(rest)?)),
+            "analyze" => {
+                let variations = args.next().and_then(|s| s.parse().ok()).unwrap_or(1);
+                let seconds = args.next().and_then(|s| s.parse().ok()).unwrap_or(5);
+                Ok(Command::Analyze { variations, seconds })
+            },
+            "finish" => Ok(Command::Finish),
+            "goodbye" => Ok(Command::Goodbye),
+            other => Err(format!("Unrecognised command: {other}"))
+        }
+    }
+}
+
+impl Display for Command {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Command::Hello => write!(f, "hello"),
+            Command::Rules(rules) => write!(f, "rules {rules}"),
+            Command::Position(position) => write!(f, "position {position}"),
+            Command::Side(side) => write!(f, "side {}", side_name(*side)),
+            Command::Clock {
+                attacker_ms,
+                defender_ms,
+                overtime_ms,
+                attacker_overtimes,
+                defender_overtimes
+            } => write!(f, "clock {attacker_ms} {defender_ms} {overtime_ms} \
+                            {attacker_overtimes} {defender_overtimes}"),
+            Command::Move(play) => write!(f, "move {play}"),
+            Command::Play(side) => write!(f, "play {}", side_name(*side)),
+            Command::Analyze { variations, seconds } => write!(f, "analyze {variations} {seconds}"),
+            Command::Finish => write!(f, "finish"),
+            Command::Goodbye => write!(f, "goodbye")
+        }
+    }
+}
+
+impl FromStr for Reply {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim();
+        let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        match name {
+            "hello" => Ok(Reply::Hello),
+            "move" => Ok(Reply::Move(strip_captures(rest).to_string())),
+            "analysis" => {
+                let mut args = rest.split_whitespace();
+                let score = args.next()
+                    .and_then(|s| s.parse().ok())
+                    .ok_or_else(|| format!("Bad analysis: {rest}"))?;
+                Ok(Reply::Analysis {
+                    score,
+                    pv: args.map(|p| strip_captures(p).to_string()).collect()
+                })
+            },
+            "error" => Ok(Reply::Error(rest.to_string())),
+            other => Err(format!("Unrecognised reply: {other}"))
+        }
+    }
+}
+
+impl Display for Reply {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reply::Hello => write!(f, "hello"),
+            Reply::Move(play) => write!(f, "move {play}"),
+            Reply::Analysis { score, pv } => write!(f, "analysis {score} {}", pv.join(" ")),
+            Reply::Error(msg) => write!(f, "error {msg}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_side, position_fen, position_string, rules_field, rules_string, Command, Reply
+    };
+    use hnefatafl::pieces::Side::{Attacker, Defender};
+
+    const BRANDUBH: &str = "3t3/3t3/3T3/ttTKTtt/3T3/3t3/3t3";
+
+    #[test]
+    fn commands_round_trip() {
+        let commands = [
+            Command::Hello,
+            Command::Rules(rules_string("Brandubh", 7, BRANDUBH)),
+            Command::Position(position_string(BRANDUBH)),
+            Command::Side(Attacker),
+            Command::Side(Defender),
+            Command::Clock {
+                attacker_ms: 60_000,
+                defender_ms: 45_500,
+                overtime_ms: 10_000,
+                attacker_overtimes: 3,
+                defender_overtimes: 2
+            },
+            Command::Move("d1-d3".to_string()),
+            Command::Play(Attacker),
+            Command::Play(Defender),
+            Command::Analyze { variations: 2, seconds: 10 },
+            Command::Finish,
+            Command::Goodbye,
+        ];
+        for command in commands {
+            assert_eq!(command.to_string().parse::<Command>(), Ok(command.clone()));
+        }
+    }
+
+    #[test]
+    fn replies_round_trip() {
+        let replies = [
+            Reply::Hello,
+            Reply::Move("d1-d3".to_string()),
+            Reply::Analysis { score: -42, pv: vec!["d1-d3".to_string(), "c4-c2".to_string()] },
+            Reply::Analysis { score: 0, pv: Vec::new() },
+            Reply::Error("It is not the defenders' turn".to_string()),
+        ];
+        for reply in replies {
+            assert_eq!(reply.to_string().parse::<Reply>(), Ok(reply.clone()));
+        }
+    }
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!("side defender".parse::<Command>(), Ok(Command::Side(Defender)));
+        assert!("side both".parse::<Command>().is_err());
+        assert!("play".parse::<Command>().is_err());
+        assert_eq!(
+            "clock 1000* 2000 500 1 0*".parse::<Command>(),
+            Ok(Command::Clock {
+                attacker_ms: 1000,
+                defender_ms: 2000,
+                overtime_ms: 500,
+                attacker_overtimes: 1,
+                defender_overtimes: 0
+            })
+        );
+        assert_eq!(
+            "clock 1000 2000".parse::<Command>(),
+            Ok(Command::Clock {
+                attacker_ms: 1000,
+                defender_ms: 2000,
+                overtime_ms: 0,
+                attacker_overtimes: 0,
+                defender_overtimes: 0
+            })
+        );
+        assert!("clock 1000".parse::<Command>().is_err());
+        assert!("clock soon 2000".parse::<Command>().is_err());
+        assert_eq!(
+            "analyze".parse::<Command>(),
+            Ok(Command::Analyze { variations: 1, seconds: 5 })
+        );
+        assert_eq!("move d1-d3xc3".parse::<Command>(), Ok(Command::Move("d1-d3".to_string())));
+        assert!("dance".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn parse_replies() {
+        assert_eq!(
+            "analysis 15 d1-d3xc3 c4-c2".parse::<Reply>(),
+            Ok(Reply::Analysis { score: 15, pv: vec!["d1-d3".to_string(), "c4-c2".to_string()] })
+        );
+        assert!("analysis".parse::<Reply>().is_err());
+        assert!("status ok".parse::<Reply>().is_err());
+    }
+
+    #[test]
+    fn sides() {
+        assert_eq!(parse_side("attackers"), Ok(Attacker));
+        assert_eq!(parse_side("defenders"), Ok(Defender));
+        assert!(parse_side("kings").is_err());
+    }
+
+    #[test]
+    fn rules_and_positions() {
+        let rules = rules_string("Brandubh", 7, BRANDUBH);
+        assert_eq!(rules_field(&rules, "dim"), Some("7"));
+        assert_eq!(rules_field(&rules, "name"), Some("Brandubh"));
+        assert_eq!(rules_field(&rules, "start").map(position_fen), Some(BRANDUBH));
+        assert_eq!(rules_field(&rules, "esc"), None);
+        assert_eq!(position_fen(&position_string(BRANDUBH)), BRANDUBH);
+    }
+}
@@ -0,0 +1,244 @@
+//! External engines: separate programs, speaking the OpenTafl Engine Protocol (see
+//! [`taflr::engine_protocol`]), which play for players whose engine is [`AiEngine::External`].
+//!
+//! Each engine is started when it is first asked for a play, told the rules of the game, and then
+//! kept running until a game with different rules is set up. Before each play, it is sent the
+//! starting position and every play made since, so that it knows the game's history (eg, for
+//! repetition rules) even if plays have been undone. The time it has to play is given as a single
+//! overtime period on an otherwise empty clock.
+//!
+//! Not available on the web, where other programs can't be run.
+//!
+//! [`AiEngine::External`]: crate::gamectrl::AiEngine::External
+
+use crate::ai::{CancelToken, SearchInfo};
+use crate::aictrl::{AiRequest, AiResponse, AiResult};
+use crate::gamectrl::ExternalEngine;
+use crate::variants::Variant;
+use dioxus::prelude::{ReadableExt, Signal, WritableExt};
+use dioxus::signals::GlobalSignal;
+use hnefatafl::aliases::MediumBasicBoardState;
+use hnefatafl::game::logic::GameLogic;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Stdio};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use taflr::engine_protocol::{position_string, rules_string, Command, Reply};
+use taflr::worker::resolve_play;
+use tokio::sync::oneshot;
+
+/// How often to check whether a search should be cancelled while waiting for the engine.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long past its time to play an engine may take before it is given up on.
+const REPLY_GRACE: Duration = Duration::from_secs(10);
+
+/// How long an engine has to exit after being told to, before it is killed.
+const EXIT_GRACE: Duration = Duration::from_secs(1);
+
+/// The game that external engines are set up to play: its logic, its rules in OpenTafl's rules
+/// notation, and its starting board.
+#[derive(Clone)]
+struct EngineGame {
+    logic: GameLogic<MediumBasicBoardState>,
+    rules: String,
+    starting_board: String,
+}
+
+static GAME: GlobalSignal<Option<EngineGame>> = Signal::global(|| None);
+
+/// Engines which are running but not searching. An engine is taken from here for each search
+/// and put back when it has played.
+static IDLE_ENGINES: Mutex<Vec<EngineProcess>> = Mutex::new(Vec::new());
+
+/// A running engine.
+struct EngineProcess {
+    /// The program being run.
+    engine: ExternalEngine,
+    /// The rules the engine was given when it was started.
+    rules: String,
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines written by the engine, read in a separate thread.
+    lines: Receiver<String>,
+}
+
+impl EngineProcess {
+    /// Start `engine` and tell it the rules of the game it will play.
+    fn start(engine: &ExternalEngine, rules: &str) -> Result<Self, String> {
+        let mut child = std::process::Command::new(&engine.path)
+            .args(engine.args.split_whitespace())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Could not start engine {}: {e}", engine.path))?;
+        let stdin = child.stdin.take().expect("Engine's stdin should be piped");
+        let stdout = child.stdout.take().expect("Engine's stdout should be piped");
+        let (tx, lines) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break
+                };
+                if tx.send(line).is_err() {
+                    break
+                }
+            }
+        });
+        let mut process = Self {
+            engine: engine.clone(),
+            rules: rules.to_string(),
+            child,
+            stdin,
+            lines,
+        };
+        process.send(&Command::Hello)?;
+        process.send(&Command::Rules(rules.to_string()))?;
+        Ok(process)
+    }
+
+    fn send(&mut self, command: &Command) -> Result<(), String> {
+        writeln!(self.stdin, "{command}")
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| format!("Could not write to engine {}: {e}", self.engine.path))
+    }
+
+    /// Ask the engine for a play, given the game so far. Returns `Ok(None)` if `cancel` is
+    /// cancelled before the engine replies.
+    fn play(
+        &mut self,
+        game: &EngineGame,
+        request: &AiRequest<MediumBasicBoardState>,
+        cancel: &CancelToken
+    ) -> Result<Option<String>, String> {
+        // Anything the engine wrote after its last play is no longer relevant
+        while self.lines.try_recv().is_ok() {}
+        self.send(&Command::Position(position_string(&game.starting_board)))?;
+        for play in &request.plays {
+            self.send(&Command::Move(play.to_string()))?;
+        }
+        let overtime_ms = request.time_to_play.as_millis() as u64;
+        self.send(&Command::Clock {
+            attacker_ms: 0,
+            defender_ms: 0,
+            overtime_ms,
+            attacker_overtimes: 1,
+            defender_overtimes: 1
+        })?;
+        self.send(&Command::Play(request.game_state.side_to_play))?;
+
+        let deadline = Instant::now() + request.time_to_play + REPLY_GRACE;
+        loop {
+            if cancel.is_cancelled() {
+                return Ok(None)
+            }
+            if Instant::now() >= deadline {
+                return Err(format!("Engine {} took too long to play", self.engine.path))
+            }
+            match self.lines.recv_timeout(POLL_INTERVAL) {
+                // Other messages, such as analysis, are ignored
+                Ok(line) => match line.parse() {
+                    Ok(Reply::Move(play)) => return Ok(Some(play)),
+                    Ok(Reply::Error(e)) => return Err(format!("Engine error: {e}")),
+                    _ => {}
+                },
+                Err(RecvTimeoutError::Timeout) => {},
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(format!("Engine {} exited", self.engine.path))
+                }
+            }
+        }
+    }
+
+    /// Tell the engine to exit, killing it if it doesn't.
+    fn quit(mut self) {
+        // The engine may already have exited, which is fine
+        let _ = self.send(&Command::Goodbye);
+        std::thread::spawn(move || {
+            let deadline = Instant::now() + EXIT_GRACE;
+            while Instant::now() < deadline {
+                if let Ok(Some(_)) = self.child.try_wait() {
+                    return
+                }
+                std::thread::sleep(POLL_INTERVAL);
+            }
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        });
+    }
+}
+
+/// Prepare external engines to play a game of `variant` using the given game logic. Engines set
+/// up for a different game are stopped.
+pub(crate) fn setup(logic: GameLogic<MediumBasicBoardState>, variant: &Variant) {
+    let rules = rules_string(&variant.name, logic.board_geo.side_len, &variant.starting_board);
+    let mut idle = IDLE_ENGINES.lock().expect("Engine list should not be poisoned");
+    let (stale, current): (Vec<_>, Vec<_>) = idle.drain(..).partition(|p| p.rules != rules);
+    *idle = current;
+    for process in stale {
+        process.quit();
+    }
+    *GAME.write() = Some(EngineGame {
+        logic,
+        rules,
+        starting_board: variant.starting_board.clone(),
+    });
+}
+
+/// Ask the player's external engine for a play in a new thread, returning a receiver for the
+/// result. If `cancel` is cancelled before the engine replies, the engine is stopped, as it can't
+/// be interrupted.
+pub(crate) fn search_in_thread(
+    request: AiRequest<MediumBasicBoardState>,
+    cancel: CancelToken
+) -> oneshot::Receiver<AiResult> {
+    let (tx, rx) = oneshot::channel();
+    let game = GAME.read().clone();
+    std::thread::spawn(move || {
+        let start_time = Instant::now();
+        let engine = &request.external_engine;
+        let result = match game {
+            Some(game) => search(&game, &request, engine, &cancel).and_then(|play| {
+                let Some(play) = play else {
+                    return Ok(None)
+                };
+                let play = resolve_play(&game.logic, &request.game_state, &play)
+                    .ok_or_else(|| format!("Engine {} made an invalid play: {play}", engine.path))?;
+                let info = SearchInfo { elapsed: start_time.elapsed(), ..SearchInfo::default() };
+                Ok(Some(AiResponse { game_state: request.game_state, play, info }))
+            }),
+            None => Err("No game set up for external engines".to_string())
+        };
+        // The receiver may already have been dropped if the search was cancelled
+        let _ = tx.send(result);
+    });
+    rx
+}
+
+/// Ask `engine` for a play, starting it if no idle instance of it is running.
+fn search(
+    game: &EngineGame,
+    request: &AiRequest<MediumBasicBoardState>,
+    engine: &ExternalEngine,
+    cancel: &CancelToken
+) -> Result<Option<String>, String> {
+    let idle = {
+        let mut idle = IDLE_ENGINES.lock().expect("Engine list should not be poisoned");
+        idle.iter()
+            .position(|p| &p.engine == engine && p.rules == game.rules)
+            .map(|i| idle.swap_remove(i))
+    };
+    let mut process = match idle {
+        Some(process) => process,
+        None => EngineProcess::start(engine, &game.rules)?
+    };
+    let result = process.play(game, request, cancel);
+    if matches!(result, Ok(Some(_))) {
+        IDLE_ENGINES.lock().expect("Engine list should not be poisoned").push(process);
+    } else {
+        // The engine is still searching, or something has gone wrong with it
+        process.quit();
+    }
+    result
+}
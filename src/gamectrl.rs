@@ -1,22 +1,90 @@
-use crate::ai::{Ai, BasicAi};
-use crate::aictrl::{AiResponse, AI};
+use crate::ai::{Difficulty, SearchInfo};
+use crate::aictrl::{cancel_ai_play, setup_ai, AiResponse};
+use crate::app_settings::APP_SETTINGS;
 use crate::game_settings::GameSettings;
 use crate::message::warning_msg;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ponder::start_pondering;
 use dioxus::prelude::*;
 use hnefatafl::aliases::{MediumBasicBoardState, MediumBasicGame};
 use hnefatafl::board::state::BoardState;
 use hnefatafl::error::PlayInvalid;
-use hnefatafl::game::state::GameState;
 use hnefatafl::game::{Game, GameStatus};
 use hnefatafl::pieces::Side;
 use hnefatafl::play::{Play, PlayRecord, ValidPlay};
 use hnefatafl::tiles::Tile;
 use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 #[cfg(not(target_arch = "wasm32"))]
 use std::time::{Duration, Instant};
 #[cfg(target_arch = "wasm32")]
 use web_time::{Duration, Instant};
 
+/// The AI implementation used by an AI player.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub(crate) enum AiEngine {
+    /// [`BasicAi`]: alpha-beta search with a hand-written evaluation.
+    #[default]
+    AlphaBeta,
+    /// [`MctsAi`]: Monte Carlo tree search with random playouts.
+    Mcts,
+    /// A separate program speaking the OpenTafl Engine Protocol (see [`ExternalEngine`]). Not
+    /// available on the web.
+    External,
+}
+
+impl AiEngine {
+    /// Name of the engine, for display to the user.
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            AiEngine::AlphaBeta => "Alpha-beta",
+            AiEngine::Mcts => "Monte Carlo",
+            AiEngine::External => "External engine",
+        }
+    }
+
+    /// The engines that can be chosen on this platform.
+    pub(crate) fn available() -> &'static [AiEngine] {
+        if cfg!(target_arch = "wasm32") {
+            &[AiEngine::AlphaBeta, AiEngine::Mcts]
+        } else {
+            &[AiEngine::AlphaBeta, AiEngine::Mcts, AiEngine::External]
+        }
+    }
+}
+
+impl Display for AiEngine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AiEngine::AlphaBeta => write!(f, "alpha_beta"),
+            AiEngine::Mcts => write!(f, "mcts"),
+            AiEngine::External => write!(f, "external"),
+        }
+    }
+}
+
+impl FromStr for AiEngine {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "alpha_beta" => Ok(AiEngine::AlphaBeta),
+            "mcts" => Ok(AiEngine::Mcts),
+            "external" => Ok(AiEngine::External),
+            other => Err(format!("Unknown AI engine: {other}"))
+        }
+    }
+}
+
+/// An engine program, run by a player whose engine is [`AiEngine::External`].
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub(crate) struct ExternalEngine {
+    /// Path of the executable.
+    pub(crate) path: String,
+    /// Arguments to pass to the executable, separated by whitespace.
+    pub(crate) args: String,
+}
+
 /// Information about a player
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub(crate) struct Player {
@@ -24,7 +92,13 @@ pub(crate) struct Player {
     pub(crate) name: String,
     /// If this player is an AI, the amount of time it has to make a play. If the player is not an
     /// AI, this should be `None`.
-    pub(crate) ai_play_time: Option<Duration>
+    pub(crate) ai_play_time: Option<Duration>,
+    /// If this player is an AI, the AI implementation it uses.
+    pub(crate) ai_engine: AiEngine,
+    /// If this player is an AI, how strongly it plays (only used by [`AiEngine::AlphaBeta`]).
+    pub(crate) ai_level: Difficulty,
+    /// If this player is an AI, the program it runs (only used by [`AiEngine::External`]).
+    pub(crate) external_engine: ExternalEngine,
 }
 
 impl Player {
@@ -57,14 +131,16 @@ pub(crate) struct GameController<B: 'static + BoardState + Send> {
     /// The `id` of the game in the database.
     pub(crate) db_id: i64,
     /// The last action performed which impacted on the game state (for serialising to DB).
-    pub(crate) last_action: Signal<Option<Action<B>>>
+    pub(crate) last_action: Signal<Option<Action<B>>>,
+    /// Information about the search that produced the AI's most recent play, if the last play
+    /// was made by an AI.
+    pub(crate) last_search: Signal<Option<SearchInfo>>
 }
 
 impl GameController<MediumBasicBoardState> {
     pub(crate) fn new(settings: GameSettings, game: MediumBasicGame, db_id: i64) -> Self {
-        use_effect(move || {
-            *AI.write() = Some(BasicAi::new(game.logic));
-        });
+        let variant = settings.variant.clone();
+        use_effect(move || setup_ai(db_id, game.logic, &variant, *APP_SETTINGS.read()));
 
         Self {
             settings,
@@ -73,39 +149,55 @@ impl GameController<MediumBasicBoardState> {
             movable: use_signal(HashSet::new),
             last_move_time: use_signal(Instant::now),
             db_id,
-            last_action: use_signal(move || None)
-        }
-    }
-
-    pub async fn request_ai_play(&mut self) -> Option<(ValidPlay, GameState<MediumBasicBoardState>)> {
-        let game_state = self.game.read().state;
-        let posn_history = self.game.read().position_history.clone();
-        let ai_time = self.current_player().ai_play_time;
-        if let Some(ttp) = ai_time {
-            tokio::task::spawn_blocking(move || {
-                if let Ok((vp, _)) = AI.write().as_mut().unwrap().next_play(
-                    &game_state,
-                    &posn_history,
-                    ttp
-                ) {
-                    Some((vp, game_state))
-                } else {
-                    None
-                }
-            }).await.unwrap()
-        } else {
-            None
+            last_action: use_signal(move || None),
+            last_search: use_signal(|| None)
         }
     }
 
     pub fn handle_ai_response(&mut self, ai_resp: AiResponse<MediumBasicBoardState>) -> Option<ValidPlay> {
         if ai_resp.game_state == self.game.read().state {
+            self.last_search.set(Some(ai_resp.info));
             Some(ai_resp.play)
         } else {
             None
         }
     }
 
+    /// If pondering is enabled and a human is now to play against the AI which has just played,
+    /// start the AI thinking about the position after the reply it expects.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn ponder(&self) {
+        if !APP_SETTINGS.read().ponder || self.is_ai_turn() {
+            return
+        }
+        let game = self.game.read();
+        let ai_player = match game.state.side_to_play {
+            Side::Attacker => &self.settings.defender,
+            Side::Defender => &self.settings.attacker
+        };
+        if !ai_player.is_ai() || ai_player.ai_engine != AiEngine::AlphaBeta {
+            return
+        }
+        let last_search = self.last_search.read();
+        let Some(pv) = last_search.as_ref().map(|s| s.principal_variation()) else {
+            return
+        };
+        // The play made may not be the one the search preferred, if the AI is playing at a level
+        // at which it blunders, in which case the rest of the line is meaningless.
+        let last_play = game.play_history.last().map(|pr| pr.play);
+        if let [ai_play, reply, ..] = pv {
+            if Some(ai_play.play) == last_play {
+                start_pondering(
+                    game.logic,
+                    &game.state,
+                    &game.position_history,
+                    *reply,
+                    ai_player.ai_level
+                );
+            }
+        }
+    }
+
 }
 
 impl<B: BoardState + Send> GameController<B> {
@@ -178,12 +270,15 @@ impl<B: BoardState + Send> GameController<B> {
     }
 
     /// Undo the last play. If `local_only` is `true`, the last play will only be undone locally,
-    /// and not in the database.
+    /// and not in the database. Any AI search in progress is cancelled, as its result would no
+    /// longer be relevant.
     pub fn undo_last_play(&mut self, local_only: bool) {
+        cancel_ai_play();
         self.game.write().undo_last_play();
         self.selected.set(None);
         self.movable.set(HashSet::new());
         self.last_move_time.set(Instant::now());
+        self.last_search.set(None);
         if !local_only {
             *self.last_action.write() = Some(Action::Undo);
         }
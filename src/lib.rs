@@ -0,0 +1,9 @@
+//! The parts of `taflr` that don't depend on the UI, so that they can also be used by headless
+//! tools and benchmarks.
+
+pub mod ai;
+pub mod engine_protocol;
+pub mod perft;
+pub mod selfplay;
+pub mod variants;
+pub mod worker;
@@ -1,15 +1,20 @@
 mod components;
-mod ai;
 mod gamectrl;
 mod game_settings;
+mod app_settings;
 mod aictrl;
 mod sqlite;
 mod error;
-mod variants;
 mod route;
 mod message;
+#[cfg(not(target_arch = "wasm32"))]
+mod external;
+#[cfg(not(target_arch = "wasm32"))]
+mod ponder;
 
 use dioxus::prelude::*;
+use taflr::{ai, variants};
+use crate::app_settings::APP_SETTINGS;
 use crate::components::StatusBar;
 use crate::error::DbError;
 use crate::route::Route;
@@ -53,9 +58,33 @@ fn Style() -> Element {
 //     }
 // }
 
+/// If a file of tuned evaluation parameters (as written by `taflr-tune`) exists in the working
+/// directory, store its parameters in the database, replacing those of the variants it names.
+#[cfg(not(target_arch = "wasm32"))]
+async fn import_tuned_params(db_ctrl: &mut DbController) -> Result<(), DbError> {
+    let path = std::path::Path::new(ai::TUNED_PARAMS_FILE);
+    match ai::load_tuned_params(path) {
+        Ok(tuned) => {
+            for (name, params) in tuned {
+                db_ctrl.set_eval_params(&name, &params).await?;
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+        Err(e) => eprintln!("Could not load tuned parameters from {}: {e}", path.display())
+    }
+    Ok(())
+}
+
 #[component]
 fn App() -> Element {
-    let resource: Resource<Result<DbController, DbError>> = use_resource(async || { DbController::new().await });
+    let resource: Resource<Result<DbController, DbError>> = use_resource(async || {
+        #[allow(unused_mut)]
+        let mut db_ctrl = DbController::new().await?;
+        *APP_SETTINGS.write() = db_ctrl.load_app_settings().await?;
+        #[cfg(not(target_arch = "wasm32"))]
+        import_tuned_params(&mut db_ctrl).await?;
+        Ok(db_ctrl)
+    });
     match &*resource.read_unchecked() {
         Some(Ok(db_ctrl)) => {
             use_context_provider(move || db_ctrl.clone());
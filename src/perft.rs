@@ -0,0 +1,134 @@
+//! Perft: counting the positions reachable from a given position in a given number of plies. All
+//! of the AI's work goes through [`GameLogic::iter_plays`] and [`GameLogic::do_valid_play`], so
+//! comparing the counts with known-good ones checks that move generation is correct, and timing
+//! them shows how fast it is.
+//!
+//! Positions in which the game is over have no plays, so a game that ends before the given depth
+//! contributes nothing to the count (as in chess perft). Positions are tracked as the AI tracks
+//! them, so that games ended by repetition are detected.
+//!
+//! As with [`Game::position_history`], the `posn_history` passed to these functions should end
+//! with `state` itself. It is left as it was when they return.
+//!
+//! [`Game::position_history`]: hnefatafl::game::Game
+
+use hnefatafl::board::state::BoardState;
+use hnefatafl::game::logic::GameLogic;
+use hnefatafl::game::state::{GameState, Position};
+use hnefatafl::game::GameStatus::Ongoing;
+use hnefatafl::play::ValidPlay;
+
+pub use crate::ai::legal_plays;
+
+/// The number of positions reached by playing every sequence of `depth` legal plays from `state`,
+/// whose position is the last in `posn_history`.
+pub fn perft<T: BoardState>(
+    logic: &GameLogic<T>,
+    state: GameState<T>,
+    depth: usize,
+    posn_history: &mut Vec<Position<T>>
+) -> u64 {
+    if depth == 0 {
+        return 1
+    }
+    if state.status != Ongoing {
+        return 0
+    }
+    let mut count = 0;
+    for p in legal_plays(logic, &state) {
+        count += child_perft(logic, p, state, depth - 1, posn_history);
+    }
+    count
+}
+
+/// The perft count (see [`perft`]) after each legal play from `state`, which add up to the count
+/// for `state`. Comparing these with known-good counts narrows down where move generation goes
+/// wrong.
+pub fn divide<T: BoardState>(
+    logic: &GameLogic<T>,
+    state: GameState<T>,
+    depth: usize,
+    posn_history: &mut Vec<Position<T>>
+) -> Vec<(ValidPlay, u64)> {
+    if depth == 0 || state.status != Ongoing {
+        return Vec::new()
+    }
+    legal_plays(logic, &state).into_iter()
+        .map(|p| (p, child_perft(logic, p, state, depth - 1, posn_history)))
+        .collect()
+}
+
+/// The perft count to `depth` from the position reached by making `play` in `state`.
+fn child_perft<T: BoardState>(
+    logic: &GameLogic<T>,
+    play: ValidPlay,
+    state: GameState<T>,
+    depth: usize,
+    posn_history: &mut Vec<Position<T>>
+) -> u64 {
+    let child = logic.do_valid_play(play, state, Some(posn_history)).new_state;
+    posn_history.push((&child).into());
+    let count = perft(logic, child, depth, posn_history);
+    posn_history.pop();
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{divide, legal_plays, perft};
+    use crate::variants::OOTB_VARIANTS;
+    use hnefatafl::aliases::MediumBasicGame;
+
+    fn new_game(name: &str) -> MediumBasicGame {
+        let (rules, board, _, _) = OOTB_VARIANTS.into_iter()
+            .find(|(_, _, n, _)| *n == name)
+            .expect("Unknown variant");
+        MediumBasicGame::new(rules, board).expect("Could not construct game")
+    }
+
+    /// Check the perft counts from the starting position of the variant called `name` to depths 1,
+    /// 2, and so on.
+    fn check_counts(name: &str, expected: &[u64]) {
+        let game = new_game(name);
+        for (depth, &expected) in (1..).zip(expected) {
+            let mut posn_history = game.position_history.clone();
+            let nodes = perft(&game.logic, game.state, depth, &mut posn_history);
+            assert_eq!(nodes, expected, "{name} to depth {depth}");
+            assert_eq!(posn_history.len(), game.position_history.len());
+        }
+    }
+
+    #[test]
+    fn copenhagen_counts() {
+        check_counts("Copenhagen", &[116, 6788, 806344]);
+    }
+
+    #[test]
+    fn tablut_counts() {
+        check_counts("Tablut", &[80, 4400, 353200]);
+    }
+
+    #[test]
+    fn brandubh_counts() {
+        check_counts("Brandubh", &[40, 960, 39512]);
+    }
+
+    #[test]
+    fn magpie_counts() {
+        // The king can't move in the first three plies, so how it moves doesn't affect these
+        check_counts("Magpie", &[40, 960, 39512]);
+    }
+
+    #[test]
+    fn divide_adds_up_to_perft() {
+        for (rules, board, name, _) in OOTB_VARIANTS {
+            let game = MediumBasicGame::new(rules, board).expect("Could not construct game");
+            let mut posn_history = game.position_history.clone();
+            let counts = divide(&game.logic, game.state, 2, &mut posn_history);
+            assert_eq!(counts.len(), legal_plays(&game.logic, &game.state).len(), "{name}");
+            let total = counts.iter().map(|(_, n)| n).sum::<u64>();
+            assert_eq!(total, perft(&game.logic, game.state, 2, &mut posn_history), "{name}");
+            assert_eq!(posn_history.len(), game.position_history.len(), "{name}");
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Pondering: letting the AI think on its opponent's time. After the AI plays against a human, it
+//! guesses the human's reply from its principal variation and searches the position that reply
+//! would lead to. If the guess is right, the AI carries on with that search instead of starting a
+//! new one. If not, the search is abandoned, but the transposition table it filled (which is
+//! shared with [`AI`]) still helps the new search.
+//!
+//! Not available on the web, where the AI can only run one search at a time.
+
+use crate::ai::{AiError, CancelToken, Difficulty, SearchInfo, SearchProgress};
+use crate::aictrl::{AiRequest, AiResponse, AiResult, AI};
+use crate::gamectrl::AiEngine;
+use dioxus::prelude::{ReadableExt, Signal, WritableExt};
+use dioxus::signals::GlobalSignal;
+use hnefatafl::aliases::MediumBasicBoardState;
+use hnefatafl::game::logic::GameLogic;
+use hnefatafl::game::state::{GameState, Position};
+use hnefatafl::game::GameStatus::Ongoing;
+use hnefatafl::play::ValidPlay;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot::error::TryRecvError;
+use tokio::sync::{oneshot, watch};
+
+/// How often to check whether a search taken over from pondering should stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The search the AI is running on its opponent's time, if any.
+static PONDER: GlobalSignal<Option<Ponder>> = Signal::global(|| None);
+
+/// A search of the position the AI expects its opponent to play into.
+pub(crate) struct Ponder {
+    /// The position being searched.
+    game_state: GameState<MediumBasicBoardState>,
+    /// The level at which the AI is searching.
+    difficulty: Difficulty,
+    /// Token for telling the search to stop and return its result.
+    stop: CancelToken,
+    progress_rx: watch::Receiver<Option<SearchProgress>>,
+    result_rx: oneshot::Receiver<Result<(ValidPlay, SearchInfo), AiError>>,
+}
+
+impl Ponder {
+    /// Whether this search can be used to answer `request`.
+    fn matches(&self, request: &AiRequest<MediumBasicBoardState>) -> bool {
+        request.engine == AiEngine::AlphaBeta
+            && request.difficulty == self.difficulty
+            && request.game_state == self.game_state
+    }
+
+    /// Let the search carry on for `time_to_play`, as if it had just been started, then return
+    /// its result. If `cancel` is cancelled first, the result is reported as cancelled.
+    ///
+    /// Returns receivers for the result and for progress updates.
+    pub(crate) fn finish(
+        self,
+        time_to_play: Duration,
+        cancel: CancelToken
+    ) -> (oneshot::Receiver<AiResult>, watch::Receiver<Option<SearchProgress>>) {
+        let (tx, rx) = oneshot::channel();
+        let Ponder { game_state, stop, mut result_rx, progress_rx, .. } = self;
+        std::thread::spawn(move || {
+            let deadline = Instant::now() + time_to_play;
+            let result = loop {
+                match result_rx.try_recv() {
+                    Ok(result) => break Some(result),
+                    Err(TryRecvError::Closed) => break None,
+                    Err(TryRecvError::Empty) => {}
+                }
+                if cancel.is_cancelled() || Instant::now() >= deadline {
+                    stop.cancel();
+                    break result_rx.blocking_recv().ok()
+                }
+                std::thread::sleep(POLL_INTERVAL);
+            };
+            let response = if cancel.is_cancelled() {
+                Ok(None)
+            } else {
+                match result {
+                    Some(Ok((play, info))) => Ok(Some(AiResponse { game_state, play, info })),
+                    Some(Err(e)) => Err(format!("Bad AI play: {:?}", e)),
+                    None => Err("Pondering thread failed".to_string())
+                }
+            };
+            // Nobody may be waiting for the result any more, which is fine
+            let _ = tx.send(response);
+        });
+        (rx, progress_rx)
+    }
+}
+
+/// Start pondering the position reached if `reply` is played in `game_state`, whose history is
+/// `posn_history` (which, like the game's `position_history`, ends with `game_state` itself). Any
+/// search already being pondered is abandoned.
+pub(crate) fn start_pondering(
+    logic: GameLogic<MediumBasicBoardState>,
+    game_state: &GameState<MediumBasicBoardState>,
+    posn_history: &[Position<MediumBasicBoardState>],
+    reply: ValidPlay,
+    difficulty: Difficulty
+) {
+    stop_pondering();
+    let Some(mut ai) = AI.read().clone() else {
+        return
+    };
+    ai.set_difficulty(difficulty);
+    // Build the history as the game would after the reply, so that the search sees the same
+    // repetitions as the request that follows it. `posn_history` already includes `game_state`.
+    let mut posns = posn_history.to_vec();
+    let expected_state = logic.do_valid_play(reply, *game_state, Some(&mut posns)).new_state;
+    if expected_state.status != Ongoing {
+        return
+    }
+    let piece = game_state.board.get_piece(reply.play.from).expect("No piece to move.");
+    if logic.get_captures(reply, piece, game_state).occupied().next().is_some() {
+        // Positions before a capture can't be repeated
+        posns.clear();
+    }
+    posns.push((&expected_state).into());
+
+    let stop = CancelToken::new();
+    let (progress_tx, progress_rx) = watch::channel(None);
+    let (tx, result_rx) = oneshot::channel();
+    let thread_stop = stop.clone();
+    std::thread::spawn(move || {
+        let result = ai.ponder(&expected_state, &posns, &thread_stop, &mut |p| {
+            // Nobody may be listening, which is fine
+            let _ = progress_tx.send(Some(*p));
+        });
+        let _ = tx.send(result);
+    });
+    *PONDER.write() = Some(Ponder {
+        game_state: expected_state,
+        difficulty,
+        stop,
+        progress_rx,
+        result_rx,
+    });
+}
+
+/// Abandon the search being pondered, if any.
+pub(crate) fn stop_pondering() {
+    if let Some(ponder) = PONDER.write().take() {
+        ponder.stop.cancel();
+    }
+}
+
+/// If the search being pondered can be used to answer `request`, take it. Otherwise, abandon it.
+pub(crate) fn take_ponder_hit(request: &AiRequest<MediumBasicBoardState>) -> Option<Ponder> {
+    let ponder = PONDER.write().take()?;
+    if ponder.matches(request) {
+        Some(ponder)
+    } else {
+        ponder.stop.cancel();
+        None
+    }
+}
@@ -4,6 +4,7 @@ use crate::components::About;
 use crate::components::NewGame;
 use crate::components::PlayGame;
 use crate::components::LoadGame;
+use crate::components::Settings;
 
 #[derive(Routable, Clone, Copy, PartialEq)]
 pub(crate) enum Route {
@@ -15,6 +16,8 @@ pub(crate) enum Route {
     LoadGame,
     #[route("/game/:id")]
     PlayGame { id: i64 },
+    #[route("/settings")]
+    Settings,
     #[route("/about")]
     About,
     #[route("/quit")]
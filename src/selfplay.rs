@@ -0,0 +1,80 @@
+//! Playing complete games between AIs without a user interface, for tuning and testing.
+
+use crate::ai::{legal_plays, Ai, CancelToken};
+use hnefatafl::board::state::BoardState;
+use hnefatafl::game::state::GameState;
+use hnefatafl::game::Game;
+use hnefatafl::game::GameOutcome::Win;
+use hnefatafl::game::GameStatus::Over;
+use hnefatafl::pieces::Side;
+use hnefatafl::pieces::Side::Attacker;
+use hnefatafl::play::Play;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::time::Duration;
+
+/// The record of a game played by [`play_game`].
+pub struct SelfPlayGame<T: BoardState> {
+    /// The winning side, or `None` if the game was drawn (including if it was stopped early).
+    pub winner: Option<Side>,
+    /// Every state in which one of the AIs was asked to play, in order.
+    pub states: Vec<GameState<T>>,
+    /// Total number of plays made, including any random opening plays.
+    pub n_plays: usize,
+    /// Every play made, in order.
+    pub plays: Vec<Play>,
+}
+
+/// Play `game` to completion between `attacker` and `defender`, each given `time_to_play` per
+/// play.
+///
+/// The first `random_plies` plays are chosen at random rather than by the AIs, so that repeated
+/// games between the same AIs don't all follow the same course. The game is treated as a draw if
+/// it hasn't ended after `max_plays` plays, or if an AI fails to find a play.
+pub fn play_game<T, A, D>(
+    mut game: Game<T>,
+    attacker: &mut A,
+    defender: &mut D,
+    time_to_play: Duration,
+    max_plays: usize,
+    random_plies: usize,
+    rng: &mut impl Rng
+) -> SelfPlayGame<T>
+where
+    T: BoardState,
+    A: Ai<BoardState = T>,
+    D: Ai<BoardState = T>,
+{
+    let mut states = Vec::new();
+    let mut plays = Vec::new();
+    let mut n_plays = 0;
+    let cancel = CancelToken::new();
+    while !matches!(game.state.status, Over(_)) && n_plays < max_plays {
+        let play = if n_plays < random_plies {
+            legal_plays(&game.logic, &game.state).choose(rng).copied()
+        } else {
+            states.push(game.state);
+            let ai_result = if game.state.side_to_play == Attacker {
+                attacker.next_play(
+                    &game.state, &game.position_history, time_to_play, &cancel, &mut |_| {}
+                )
+            } else {
+                defender.next_play(
+                    &game.state, &game.position_history, time_to_play, &cancel, &mut |_| {}
+                )
+            };
+            ai_result.ok().map(|(p, _)| p)
+        };
+        let Some(play) = play else {
+            break
+        };
+        game.do_play(play.play).expect("AI made an invalid play");
+        plays.push(play.play);
+        n_plays += 1;
+    }
+    let winner = match game.state.status {
+        Over(Win(_, side)) => Some(side),
+        _ => None
+    };
+    SelfPlayGame { winner, states, n_plays, plays }
+}
@@ -1,7 +1,9 @@
 use std::iter::zip;
+use crate::app_settings::{AppSettings, MAX_TT_SIZE_MB, MIN_TT_SIZE_MB};
 use crate::error::DbError;
 use crate::game_settings::GameSettings;
-use crate::gamectrl::Player;
+use crate::gamectrl::{AiEngine, ExternalEngine, Player};
+use crate::ai::{Difficulty, EvalParams};
 use crate::variants::{Variant, OOTB_VARIANTS};
 use hnefatafl::board::state::BoardState;
 use hnefatafl::collections::PieceMap;
@@ -16,15 +18,51 @@ use std::time::Duration;
 
 const DB_PATH: &str = "sqlite://taflr.sqlite";
 
-impl<'r> sqlx::FromRow<'r, SqliteRow> for Variant {
-    fn from_row(row: &'r SqliteRow) -> Result<Self, Error> {
-        Ok(Self {
-            rules: serde_json::from_str(row.try_get("rules")?).expect("Bad JSON"),
-            starting_board: row.try_get("starting_board")?,
-            name: row.try_get("name")?,
-            is_custom: row.try_get("is_custom")?,
-        })
-    }
+/// Construct a [`Variant`] from a row of the `variants` table. (`Variant` is defined in the library
+/// crate, so we can't implement [`FromRow`] for it here.)
+///
+/// Variants saved before evaluation parameters were stored have none, in which case we use the
+/// parameters for the out-of-the-box variant of the same name, or the defaults.
+fn variant_from_row(row: &SqliteRow) -> Result<Variant, Error> {
+    let name: String = row.try_get("name")?;
+    let eval_params = match row.try_get::<'_, Option<&str>, _>("eval_params")? {
+        Some(s) => serde_json::from_str(s).expect("Bad JSON"),
+        None => Variant::from_str(&name).map(|v| v.eval_params).unwrap_or_default()
+    };
+    Ok(Variant {
+        rules: serde_json::from_str(row.try_get("rules")?).expect("Bad JSON"),
+        starting_board: row.try_get("starting_board")?,
+        name,
+        is_custom: row.try_get("is_custom")?,
+        eval_params,
+    })
+}
+
+/// Construct a [`Player`] from the columns of a row of the `games` table that describe the player
+/// on the given side (`"attacker"` or `"defender"`).
+fn player_from_row(row: &SqliteRow, side: &str) -> Result<Player, Error> {
+    Ok(Player {
+        name: row.try_get(format!("{side}_name").as_str())?,
+        ai_play_time: row.try_get::<'_, Option<i64>, _>(format!("{side}_ai_ttp").as_str())?
+            .map(|s| Duration::from_secs(s as u64)),
+        // Games saved before the engine was stored used the alpha-beta engine
+        ai_engine: row.try_get::<'_, Option<&str>, _>(format!("{side}_ai_engine").as_str())?
+            .map(AiEngine::from_str)
+            .transpose()
+            .map_err(|e| Error::Decode(e.into()))?
+            .unwrap_or_default(),
+        ai_level: row.try_get::<'_, Option<&str>, _>(format!("{side}_ai_level").as_str())?
+            .map(Difficulty::from_str)
+            .transpose()
+            .map_err(|e| Error::Decode(e.into()))?
+            .unwrap_or_default(),
+        external_engine: ExternalEngine {
+            path: row.try_get::<'_, Option<String>, _>(format!("{side}_engine_path").as_str())?
+                .unwrap_or_default(),
+            args: row.try_get::<'_, Option<String>, _>(format!("{side}_engine_args").as_str())?
+                .unwrap_or_default(),
+        },
+    })
 }
 
 pub(crate) struct GameSettingsDbObject {
@@ -38,16 +76,8 @@ impl<'r> GameSettingsDbObject {
         let game_settings = GameSettings {
             variant,
             name: row.try_get("name")?,
-            attacker: Player {
-                name: row.try_get("attacker_name")?,
-                ai_play_time: row.try_get::<'_, Option<i64>, _>("attacker_ai_ttp")?
-                    .map(|s| Duration::from_secs(s as u64)),
-            },
-            defender: Player {
-                name: row.try_get("defender_name")?,
-                ai_play_time: row.try_get::<'_, Option<i64>, _>("defender_ai_ttp")?
-                    .map(|s| Duration::from_secs(s as u64)),
-            },
+            attacker: player_from_row(row, "attacker")?,
+            defender: player_from_row(row, "defender")?,
         };
         Ok(Self {
             id,
@@ -92,16 +122,8 @@ impl<'r> FromRow<'r, SqliteRow> for SavedGameInfo {
             id: row.try_get("id")?,
             game_name: row.try_get("game_name")?,
             variant_name: row.try_get("variant_name")?,
-            attacker: Player {
-                name: row.try_get("attacker_name")?,
-                ai_play_time: row.try_get::<'_, Option<i64>, _>("attacker_ai_ttp")?
-                    .map(|s| Duration::from_secs(s as u64)),
-            },
-            defender: Player {
-                name: row.try_get("defender_name")?,
-                ai_play_time: row.try_get::<'_, Option<i64>, _>("defender_ai_ttp")?
-                    .map(|s| Duration::from_secs(s as u64)),
-            },
+            attacker: player_from_row(row, "attacker")?,
+            defender: player_from_row(row, "defender")?,
             board_state: row.try_get("board")?,
             turn: row.try_get::<i64, _>("turn")? as u64,
             side_to_play: Side::from_str(row.try_get("side_to_play")?)
@@ -141,16 +163,48 @@ impl DbController {
 
     pub(crate) async fn create_schemas(&mut self) -> Result<(), sqlx::Error> {
         sqlx::query(include_str!("../sql/schema.sqlite")).execute(&self.pool).await?;
+        // Databases created by older versions may lack some columns
+        self.add_column_if_missing("variants", "eval_params", "TEXT").await?;
+        self.add_column_if_missing("games", "attacker_ai_engine", "TEXT").await?;
+        self.add_column_if_missing("games", "defender_ai_engine", "TEXT").await?;
+        self.add_column_if_missing("games", "attacker_ai_level", "TEXT").await?;
+        self.add_column_if_missing("games", "defender_ai_level", "TEXT").await?;
+        self.add_column_if_missing("games", "attacker_engine_path", "TEXT").await?;
+        self.add_column_if_missing("games", "attacker_engine_args", "TEXT").await?;
+        self.add_column_if_missing("games", "defender_engine_path", "TEXT").await?;
+        self.add_column_if_missing("games", "defender_engine_args", "TEXT").await?;
+        Ok(())
+    }
+
+    /// Add a column with the given name and type to `table`, unless it already has one.
+    async fn add_column_if_missing(
+        &mut self,
+        table: &str,
+        column: &str,
+        col_type: &str
+    ) -> Result<(), sqlx::Error> {
+        let exists: bool = query(r"SELECT COUNT(*) > 0 FROM pragma_table_info(?) WHERE name = ?")
+            .bind(table)
+            .bind(column)
+            .fetch_one(&self.pool)
+            .await?
+            .try_get(0)?;
+        if !exists {
+            sqlx::query(&format!("ALTER TABLE {table} ADD COLUMN {column} {col_type}"))
+                .execute(&self.pool)
+                .await?;
+        }
         Ok(())
     }
 
     pub(crate) async fn populate_tables(&mut self) -> Result<(), DbError> {
-        for (rules, starting_board, name) in OOTB_VARIANTS {
+        for (rules, starting_board, name, eval_params) in OOTB_VARIANTS {
             self.add_variant(Variant {
                 rules,
                 starting_board: starting_board.to_string(),
                 name: name.to_string(),
                 is_custom: false,
+                eval_params,
             }).await?;
         }
         Ok(())
@@ -162,6 +216,12 @@ impl DbController {
         self.add_variant(settings.variant).await?;
         let att_ai_ttp = settings.attacker.ai_play_time.map(|d| d.as_secs_f64());
         let def_ai_ttp = settings.defender.ai_play_time.map(|d| d.as_secs_f64());
+        let att_ai_engine = settings.attacker.ai_engine.to_string();
+        let def_ai_engine = settings.defender.ai_engine.to_string();
+        let att_ai_level = settings.attacker.ai_level.to_string();
+        let def_ai_level = settings.defender.ai_level.to_string();
+        let att_engine = &settings.attacker.external_engine;
+        let def_engine = &settings.defender.external_engine;
         Ok(sqlx::query!(
             r#"
                 INSERT INTO games (
@@ -170,17 +230,33 @@ impl DbController {
                     turn,
                     attacker_name,
                     attacker_ai_ttp,
+                    attacker_ai_engine,
+                    attacker_ai_level,
+                    attacker_engine_path,
+                    attacker_engine_args,
                     defender_name,
-                    defender_ai_ttp
-                ) VALUES (?, ?, ?, ?, ?, ?, ?)
+                    defender_ai_ttp,
+                    defender_ai_engine,
+                    defender_ai_level,
+                    defender_engine_path,
+                    defender_engine_args
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
             settings.name,
             variant_name,
             0,
             settings.attacker.name,
             att_ai_ttp,
+            att_ai_engine,
+            att_ai_level,
+            att_engine.path,
+            att_engine.args,
             settings.defender.name,
             def_ai_ttp,
+            def_ai_engine,
+            def_ai_level,
+            def_engine.path,
+            def_engine.args,
         ).execute(&self.pool).await?.last_insert_rowid())
     }
 
@@ -333,27 +409,44 @@ impl DbController {
 
     pub(crate) async fn add_variant(&mut self, variant: Variant) -> Result<i64, DbError> {
         let rule_str = serde_json::to_string(&variant.rules)?;
+        let eval_params_str = serde_json::to_string(&variant.eval_params)?;
         Ok(sqlx::query!(
             r#"
                 INSERT OR IGNORE INTO variants (
                     name,
                     rules,
                     starting_board,
-                    is_custom
-                ) VALUES (?, ?, ?, ?)
+                    is_custom,
+                    eval_params
+                ) VALUES (?, ?, ?, ?, ?)
             "#,
             variant.name,
             rule_str,
             variant.starting_board,
-            variant.is_custom
+            variant.is_custom,
+            eval_params_str
         ).execute(&self.pool).await?.last_insert_rowid())
     }
 
+    /// Replace the evaluation parameters stored for the named variant, eg, with tuned ones.
+    pub(crate) async fn set_eval_params(
+        &mut self,
+        variant_name: &str,
+        params: &EvalParams
+    ) -> Result<(), DbError> {
+        let params_str = serde_json::to_string(params)?;
+        query!(r"UPDATE variants SET eval_params = ? WHERE name = ?", params_str, variant_name)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
     pub(crate) async fn load_variant(&self, name: &str) -> Result<Variant, DbError> {
-        Ok(query_as(r"SELECT * FROM variants WHERE name = ?")
+        let row = query(r"SELECT * FROM variants WHERE name = ?")
             .bind(name)
             .fetch_one(&self.pool)
-            .await?)
+            .await?;
+        Ok(variant_from_row(&row)?)
     }
 
     pub(crate) async fn load_saved_game_info(&mut self) -> Result<Vec<SavedGameInfo>, DbError> {
@@ -364,8 +457,16 @@ impl DbController {
                 variants.name as variant_name,
                 games.attacker_name,
                 games.attacker_ai_ttp,
+                games.attacker_ai_engine,
+                games.attacker_ai_level,
+                games.attacker_engine_path,
+                games.attacker_engine_args,
                 games.defender_name,
                 games.defender_ai_ttp,
+                games.defender_ai_engine,
+                games.defender_ai_level,
+                games.defender_engine_path,
+                games.defender_engine_args,
                 states.board,
                 states.turn,
                 states.side_to_play,
@@ -383,6 +484,53 @@ impl DbController {
             .await?)
     }
 
+    /// The saved value of the named application setting, if any.
+    async fn load_setting(&self, key: &str) -> Result<Option<String>, DbError> {
+        Ok(query(r"SELECT value FROM settings WHERE key = ?")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await?
+            .map(|r| r.try_get("value"))
+            .transpose()?)
+    }
+
+    async fn save_setting(&mut self, key: &str, value: String) -> Result<(), DbError> {
+        query(r"INSERT OR REPLACE INTO settings (key, value) VALUES (?, ?)")
+            .bind(key)
+            .bind(value)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Load the application settings. Any settings that have not been saved (or can't be parsed)
+    /// take their default values.
+    pub(crate) async fn load_app_settings(&self) -> Result<AppSettings, DbError> {
+        let mut settings = AppSettings::default();
+        if let Some(s) = self.load_setting("tt_size_mb").await?.and_then(|s| s.parse().ok()) {
+            // The setting may have been saved before it was limited
+            settings.tt_size_mb = usize::clamp(s, MIN_TT_SIZE_MB, MAX_TT_SIZE_MB);
+        }
+        if let Some(s) = self.load_setting("search_threads").await?.and_then(|s| s.parse().ok()) {
+            settings.search_threads = s;
+        }
+        if let Some(s) = self.load_setting("ponder").await?.and_then(|s| s.parse().ok()) {
+            settings.ponder = s;
+        }
+        if let Some(s) = self.load_setting("save_tt").await?.and_then(|s| s.parse().ok()) {
+            settings.save_tt = s;
+        }
+        Ok(settings)
+    }
+
+    pub(crate) async fn save_app_settings(&mut self, settings: &AppSettings) -> Result<(), DbError> {
+        self.save_setting("tt_size_mb", settings.tt_size_mb.to_string()).await?;
+        self.save_setting("search_threads", settings.search_threads.to_string()).await?;
+        self.save_setting("ponder", settings.ponder.to_string()).await?;
+        self.save_setting("save_tt", settings.save_tt.to_string()).await?;
+        Ok(())
+    }
+
     pub(crate) async fn delete_game_from_db(&self, id: i64) -> Result<(), DbError> {
         query!(r"DELETE FROM games WHERE id = ?", id).execute(&self.pool).await?;
         query!(r"DELETE FROM states WHERE game_id = ?", id).execute(&self.pool).await?;
@@ -1,12 +1,13 @@
 use std::str::FromStr;
 use hnefatafl::preset;
 use hnefatafl::rules::Ruleset;
+use crate::ai::EvalParams;
 
-pub(crate) const OOTB_VARIANTS: [(Ruleset, &str, &str); 4] = [
-    (preset::rules::COPENHAGEN, preset::boards::COPENHAGEN, "Copenhagen"),
-    (preset::rules::TABLUT, preset::boards::TABLUT, "Tablut"),
-    (preset::rules::BRANDUBH, preset::boards::BRANDUBH, "Brandubh"),
-    (preset::rules::MAGPIE, preset::boards::MAGPIE, "Magpie"),
+pub const OOTB_VARIANTS: [(Ruleset, &str, &str, EvalParams); 4] = [
+    (preset::rules::COPENHAGEN, preset::boards::COPENHAGEN, "Copenhagen", EvalParams::COPENHAGEN),
+    (preset::rules::TABLUT, preset::boards::TABLUT, "Tablut", EvalParams::TABLUT),
+    (preset::rules::BRANDUBH, preset::boards::BRANDUBH, "Brandubh", EvalParams::BRANDUBH),
+    (preset::rules::MAGPIE, preset::boards::MAGPIE, "Magpie", EvalParams::MAGPIE),
 ];
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -15,6 +16,8 @@ pub struct Variant {
     pub starting_board: String,
     pub name: String,
     pub is_custom: bool,
+    /// Weights the AI uses to evaluate positions in this variant.
+    pub eval_params: EvalParams,
 }
 
 impl FromStr for Variant {
@@ -25,25 +28,29 @@ impl FromStr for Variant {
                 rules: preset::rules::COPENHAGEN,
                 starting_board: preset::boards::COPENHAGEN.to_string(),
                 name: "Copenhagen".to_string(),
-                is_custom: false
+                is_custom: false,
+                eval_params: EvalParams::COPENHAGEN
             }),
             "Brandubh" => Ok(Self {
                 rules: preset::rules::BRANDUBH,
                 starting_board: preset::boards::BRANDUBH.to_string(),
                 name: "Brandubh".to_string(),
-                is_custom: false
+                is_custom: false,
+                eval_params: EvalParams::BRANDUBH
             }),
             "Tablut" => Ok(Self {
                 rules: preset::rules::TABLUT,
                 starting_board: preset::boards::TABLUT.to_string(),
                 name: "Tablut".to_string(),
-                is_custom: false
+                is_custom: false,
+                eval_params: EvalParams::TABLUT
             }),
             "Magpie" => Ok(Self {
                 rules: preset::rules::MAGPIE,
                 starting_board: preset::boards::MAGPIE.to_string(),
                 name: "Magpie".to_string(),
-                is_custom: false
+                is_custom: false,
+                eval_params: EvalParams::MAGPIE
             }),
             other => Err(format!("Unknown variant: {}", other))
         }
@@ -0,0 +1,314 @@
+//! Running the AI in a web worker. Browsers don't let wasm code spawn threads, and searching on the
+//! page's own thread would freeze the page, so on the web build the AI runs in a separate worker
+//! (see [`AiWorker`]) which the app talks to by posting the messages defined here.
+//!
+//! Messages have to be serialised, so games, plays and search results are sent in the same text
+//! forms in which they are stored in the database, and converted back on the other side.
+
+use crate::ai::{legal_plays, DepthInfo, Difficulty, SearchInfo, SearchProgress, SearchStats};
+use hnefatafl::board::state::BoardState;
+use hnefatafl::game::logic::GameLogic;
+use hnefatafl::game::state::GameState;
+use hnefatafl::play::{Play, ValidPlay};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+#[cfg(target_arch = "wasm32")]
+use web_time::Duration;
+
+#[cfg(target_arch = "wasm32")]
+pub use crate::worker::web::AiWorker;
+
+/// The AI the worker should search with.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum WorkerEngine {
+    /// [`crate::ai::BasicAi`], playing at the given level.
+    AlphaBeta(Difficulty),
+    /// [`crate::ai::MctsAi`].
+    Mcts,
+}
+
+/// A message from the app to the worker.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum WorkerInput {
+    /// Prepare the AIs for a game. Must be sent before the first search of each game. If the AIs
+    /// are already set up for the same game, they are kept as they are.
+    Setup {
+        /// Identifies the game (by its id in the app's database).
+        game_id: i64,
+        /// The game's rules, as JSON.
+        rules: String,
+        /// The starting board, as FEN.
+        starting_board: String,
+        eval_params: crate::ai::EvalParams,
+        tt_size_mb: usize,
+    },
+    /// Find a play in the position reached by making `plays`, in order, from the starting
+    /// position. The worker replays the whole game so that it has the full position history.
+    Search {
+        /// Identifies the search in the worker's responses.
+        id: u64,
+        plays: Vec<String>,
+        time_to_play: Duration,
+        engine: WorkerEngine,
+    },
+}
+
+/// A message from the worker to the app.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum WorkerOutput {
+    /// The search with the given id is still under way.
+    Progress { id: u64, progress: WireProgress },
+    /// The search with the given id has finished.
+    Done { id: u64, result: Result<WireSearchResult, String> },
+}
+
+/// A serialisable [`SearchProgress`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WireProgress {
+    depth: u8,
+    nodes: u64,
+    elapsed: Duration,
+    best: Option<(String, i32)>,
+}
+
+impl WireProgress {
+    pub fn new(progress: &SearchProgress) -> Self {
+        Self {
+            depth: progress.depth,
+            nodes: progress.nodes,
+            elapsed: progress.elapsed,
+            best: progress.best.map(|(vp, score)| (vp.play.to_string(), score)),
+        }
+    }
+
+    /// Convert back to a [`SearchProgress`] for the search of `state`. If the best play can't be
+    /// made in `state`, it is left out.
+    pub fn to_progress<T: BoardState>(
+        &self,
+        logic: &GameLogic<T>,
+        state: &GameState<T>
+    ) -> SearchProgress {
+        SearchProgress {
+            depth: self.depth,
+            nodes: self.nodes,
+            elapsed: self.elapsed,
+            best: self.best.as_ref()
+                .and_then(|(play, score)| Some((resolve_play(logic, state, play)?, *score))),
+        }
+    }
+}
+
+/// A serialisable [`DepthInfo`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WireDepthInfo {
+    depth: u8,
+    score: i32,
+    pv: Vec<String>,
+    nodes: u64,
+    elapsed: Duration,
+}
+
+/// A serialisable play chosen by the AI, along with the [`SearchInfo`] for the search that chose
+/// it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WireSearchResult {
+    /// The play chosen, which may differ from the best play found if the AI is playing at a level
+    /// at which it sometimes blunders.
+    play: String,
+    depths: Vec<WireDepthInfo>,
+    stats: SearchStats,
+    elapsed: Duration,
+}
+
+impl WireSearchResult {
+    pub fn new(play: ValidPlay, info: &SearchInfo) -> Self {
+        Self {
+            play: play.play.to_string(),
+            depths: info.depths.iter()
+                .map(|d| WireDepthInfo {
+                    depth: d.depth,
+                    score: d.score,
+                    pv: d.pv.iter().map(|vp| vp.play.to_string()).collect(),
+                    nodes: d.nodes,
+                    elapsed: d.elapsed,
+                })
+                .collect(),
+            stats: info.stats,
+            elapsed: info.elapsed,
+        }
+    }
+
+    /// Convert back to the chosen play and search information, for the search of `state`.
+    /// Returns `None` if the chosen play or any principal variation can't be played from `state`.
+    pub fn to_result<T: BoardState>(
+        &self,
+        logic: &GameLogic<T>,
+        state: &GameState<T>
+    ) -> Option<(ValidPlay, SearchInfo)> {
+        let play = resolve_play(logic, state, &self.play)?;
+        let mut depths = Vec::with_capacity(self.depths.len());
+        for d in &self.depths {
+            depths.push(DepthInfo {
+                depth: d.depth,
+                score: d.score,
+                pv: resolve_line(logic, state, &d.pv)?,
+                nodes: d.nodes,
+                elapsed: d.elapsed,
+            });
+        }
+        Some((play, SearchInfo { depths, stats: self.stats, elapsed: self.elapsed }))
+    }
+}
+
+/// Find the legal play in `state` described by `play`.
+pub fn resolve_play<T: BoardState>(
+    logic: &GameLogic<T>,
+    state: &GameState<T>,
+    play: &str
+) -> Option<ValidPlay> {
+    let play = Play::from_str(play).ok()?;
+    legal_plays(logic, state).into_iter().find(|vp| vp.play == play)
+}
+
+/// Find the legal plays described by `line`, each made in the position resulting from the ones
+/// before, starting from `state`.
+fn resolve_line<T: BoardState>(
+    logic: &GameLogic<T>,
+    state: &GameState<T>,
+    line: &[String]
+) -> Option<Vec<ValidPlay>> {
+    let mut state = *state;
+    let mut plays = Vec::with_capacity(line.len());
+    for play in line {
+        let vp = resolve_play(logic, &state, play)?;
+        state = logic.do_valid_play(vp, state, None).new_state;
+        plays.push(vp);
+    }
+    Some(plays)
+}
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use crate::ai::{Ai, BasicAi, CancelToken, MctsAi};
+    use crate::worker::{WireProgress, WireSearchResult, WorkerEngine, WorkerInput, WorkerOutput};
+    use gloo_worker::{HandlerId, Worker, WorkerScope};
+    use hnefatafl::aliases::{MediumBasicBoardState, MediumBasicGame};
+    use hnefatafl::play::Play;
+    use hnefatafl::rules::Ruleset;
+    use std::str::FromStr;
+    use web_time::Duration;
+
+    /// The game being played, and the AIs set up for it.
+    struct WorkerGame {
+        game_id: i64,
+        tt_size_mb: usize,
+        rules: Ruleset,
+        starting_board: String,
+        ai: BasicAi<MediumBasicBoardState>,
+        mcts: MctsAi<MediumBasicBoardState>,
+    }
+
+    /// The web worker that runs the AI. The AIs are kept between searches (until a
+    /// [`WorkerInput::Setup`] for a different game) so that the transposition table carries over
+    /// from play to play.
+    ///
+    /// A worker handles one message at a time, so a search can't be interrupted: if the app no
+    /// longer needs its result, it replaces the worker with a new one (losing the transposition
+    /// table), and the old worker exits once the search ends.
+    pub struct AiWorker {
+        /// The game set up by the last [`WorkerInput::Setup`], or why it couldn't be set up.
+        game: Result<WorkerGame, String>,
+    }
+
+    impl AiWorker {
+        fn setup(
+            game_id: i64,
+            rules: &str,
+            starting_board: String,
+            eval_params: crate::ai::EvalParams,
+            tt_size_mb: usize
+        ) -> Result<WorkerGame, String> {
+            let rules: Ruleset = serde_json::from_str(rules)
+                .map_err(|e| format!("Bad rules: {e}"))?;
+            let logic = MediumBasicGame::new(rules, &starting_board)
+                .map_err(|e| format!("Could not construct game: {e:?}"))?
+                .logic;
+            let mut ai = BasicAi::with_tt_size(logic, tt_size_mb);
+            ai.eval_params = eval_params;
+            Ok(WorkerGame {
+                game_id,
+                tt_size_mb,
+                rules,
+                starting_board,
+                ai,
+                mcts: MctsAi::new(logic)
+            })
+        }
+
+        fn search(
+            &mut self,
+            plays: &[String],
+            time_to_play: Duration,
+            engine: WorkerEngine,
+            on_progress: &mut dyn FnMut(WireProgress)
+        ) -> Result<WireSearchResult, String> {
+            let wg = self.game.as_mut().map_err(|e| e.clone())?;
+            let mut game = MediumBasicGame::new(wg.rules, &wg.starting_board)
+                .map_err(|e| format!("Could not construct game: {e:?}"))?;
+            for play in plays {
+                let play = Play::from_str(play).map_err(|e| format!("Bad play {play}: {e:?}"))?;
+                game.do_play(play).map_err(|e| format!("Invalid play {play}: {e:?}"))?;
+            }
+            let ai: &mut dyn Ai<BoardState = MediumBasicBoardState> = match engine {
+                WorkerEngine::AlphaBeta(difficulty) => {
+                    wg.ai.set_difficulty(difficulty);
+                    &mut wg.ai
+                },
+                WorkerEngine::Mcts => &mut wg.mcts,
+            };
+            let (play, info) = ai.next_play(
+                &game.state,
+                &game.position_history,
+                time_to_play,
+                &CancelToken::new(),
+                &mut |p| on_progress(WireProgress::new(p))
+            ).map_err(|e| format!("Bad AI play: {e:?}"))?;
+            Ok(WireSearchResult::new(play, &info))
+        }
+    }
+
+    impl Worker for AiWorker {
+        type Message = ();
+        type Input = WorkerInput;
+        type Output = WorkerOutput;
+
+        fn create(_scope: &WorkerScope<Self>) -> Self {
+            Self { game: Err("AI worker was not set up".to_string()) }
+        }
+
+        fn update(&mut self, _scope: &WorkerScope<Self>, _msg: Self::Message) {}
+
+        fn received(&mut self, scope: &WorkerScope<Self>, msg: Self::Input, who: HandlerId) {
+            match msg {
+                WorkerInput::Setup { game_id, rules, starting_board, eval_params, tt_size_mb } => {
+                    let same_game = self.game.as_ref()
+                        .is_ok_and(|wg| wg.game_id == game_id && wg.tt_size_mb == tt_size_mb);
+                    if !same_game {
+                        // Any error is reported in response to the next search
+                        self.game = Self::setup(
+                            game_id, &rules, starting_board, eval_params, tt_size_mb
+                        );
+                    }
+                },
+                WorkerInput::Search { id, plays, time_to_play, engine } => {
+                    let result = self.search(&plays, time_to_play, engine, &mut |progress| {
+                        scope.respond(who, WorkerOutput::Progress { id, progress })
+                    });
+                    scope.respond(who, WorkerOutput::Done { id, result });
+                }
+            }
+        }
+    }
+}